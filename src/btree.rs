@@ -0,0 +1,656 @@
+mod btree_node;
+
+use anyhow::{
+  anyhow, Result
+};
+
+use std::collections::HashMap;
+
+use crate::{
+  page_class, HEADER_LEN, EpochGuard, EpochWorker, Page, PageGuard, PageManager, PageSWIP, PageVLDS, StorageTier, Swip, WriteGuard
+};
+
+pub use btree_node::*;
+
+// Nodes grow a page class at a time up to this one and split past it
+pub const BTREE_MAX_NODE_CID: usize = 16;
+pub const BTREE_MAX_NODE_LEN: usize = (1 << BTREE_MAX_NODE_CID) - HEADER_LEN;
+
+// Nodes shorter than this are merged with a sibling when the two fit in one
+pub const BTREE_MERGE_LEN: usize = BTREE_MAX_NODE_LEN / 4;
+
+// Small enough that splitting a full node always leaves two that fit
+pub const BTREE_MAX_VALUE_LEN: usize = BTREE_MAX_NODE_LEN / 4;
+
+//
+// A B+Tree of u64 keys over PageManager pages (see docs/design.md). Nodes
+//  start in the smallest page class and are resized into the class that
+//  fits them when they outgrow it or shrink into a smaller one, a node
+//  splits once it outgrows the largest node class and neighbours merge
+//  once one of them is short enough for the two to fit together
+//
+// Inner nodes hold each child's PID and the frame it was last written to.
+//  Lookups follow the frame address while it still holds the child and
+//  only resolve the PID through the page manager once the child was
+//  evicted or moved, writers point the parent at the child's new frame in
+//  place when they come across one that moved. The root keeps its PID for
+//  the life of the tree, it hands its contents to new children when it
+//  splits and takes over its last child's when it shrinks
+//
+// Everything uses optimistic lock coupling on the VLDS word: every node is
+//  copied out without a latch and a parent is validated after its child has
+//  been read, any change restarts the descent from the root. Writers work
+//  out their change on the copies and then only latch the nodes they write
+//  or free, without waiting, and restart unless every one of them is still
+//  at the version it was read at. Nodes are written in an order that keeps
+//  every step a consistent tree for lookups: a split publishes the new node
+//  in the parent before the split node shrinks and a merge fills the merged
+//  node before the parent drops its sibling, so a lookup only ever sees a
+//  node that still holds everything below it
+//
+
+// Separator and the (PID, frame address) of a node split off another
+type Split = Option<(u64, (usize, usize))>;
+
+// A node's page, its copy and the version the copy was read at
+type Level<'a> = (PageGuard<'a>, BTreeNode, usize);
+
+// A node's page reached through its parent's frame address or fetched by PID
+#[derive(Debug)]
+enum NodePage<'e, 'a> {
+  Swizzled(Page<'e>),
+  Fetched(PageGuard<'a>)
+}
+
+impl<'e, 'a: 'e> NodePage<'e, 'a> {
+  fn page(&self) -> &Page<'e> {
+    match self {
+      Self::Swizzled(page) => page,
+      Self::Fetched(page) => page.page()
+    }
+  }
+}
+
+// Pages and root PID
+#[derive(Debug)]
+pub struct BTree<'a>(&'a PageManager, usize);
+
+impl<'a> BTree<'a> {
+  fn pages(&self) -> &'a PageManager {
+    self.0
+  }
+
+  pub fn root(&self) -> usize {
+    self.1
+  }
+
+  pub fn try_get(&self, worker: &mut EpochWorker, key: u64) -> Result<Option<Vec<u8>>> {
+    loop {
+      if let Some(found) = self.try_get_optimistic(&worker.enter(), key)? {
+        return Ok(found)
+      }
+    }
+  }
+
+  // Returns the value the key replaced
+  pub fn try_insert(&self, worker: &mut EpochWorker, key: u64, value: &[u8]) -> Result<Option<Vec<u8>>> {
+    if value.len() > BTREE_MAX_VALUE_LEN {
+      return Err(anyhow!("Value of {} bytes is over the {} byte limit", value.len(), BTREE_MAX_VALUE_LEN))
+    }
+
+    loop {
+      if let Some(replaced) = self.try_insert_optimistic(&worker.enter(), key, value)? {
+        return Ok(replaced)
+      }
+    }
+  }
+
+  pub fn try_remove(&self, worker: &mut EpochWorker, key: u64) -> Result<Option<Vec<u8>>> {
+    loop {
+      if let Some(removed) = self.try_remove_optimistic(&worker.enter(), key)? {
+        return Ok(removed)
+      }
+    }
+  }
+
+  pub fn try_free(self) -> Result<()> {
+    self.try_free_node(self.root())
+  }
+
+  // The root becomes an inner node once it splits and keeps its PID, so it starts out in the index tier
+  pub fn try_create(pages: &'a PageManager) -> Result<Self> {
+    let mut page = pages.try_alloc_in(BTREE_NODE_HEADER_LEN as u32, StorageTier::Index)?;
+    BTreeNode::Leaf(vec![], 0).try_write(&mut page.try_write()?)?;
+
+    Ok(Self::open(pages, page.pid()))
+  }
+
+  pub fn open(pages: &'a PageManager, root: usize) -> Self {
+    Self(pages, root)
+  }
+
+  // Private Helpers
+
+  // None when a writer got in the way and the descent has to start over from the root
+  fn try_get_optimistic(&self, epoch: &EpochGuard, key: u64) -> Result<Option<Option<Vec<u8>>>> {
+    let mut parent: Option<(NodePage<'_, 'a>, usize)> = None;
+    let mut child = Ok(NodePage::Fetched(self.pages().try_fetch(self.root())?));
+
+    loop {
+      // A child that was freed or moved since its parent was read is only an error when the parent still points at it
+      let read = child.and_then(|page| Ok((BTreeNode::try_read_optimistic(self.pages(), page.page(), epoch)?, page)));
+      let changed = parent.as_ref().is_some_and(|(parent, version)| !Self::is_unchanged(parent.page(), *version));
+
+      let (node, version, page) = match read {
+        Ok((Some((node, version)), page)) if !changed => (node, version, page),
+        Ok(_) => return Ok(None),
+        Err(_) if changed => return Ok(None),
+        Err(err) => return Err(err)
+      };
+
+      match node {
+        BTreeNode::Leaf(mut entries, _) => {
+          let found = entries.binary_search_by_key(&key, |(key, _)| *key).ok();
+          return Ok(Some(found.map(|idx| entries.swap_remove(idx).1)))
+        }
+
+        BTreeNode::Inner(keys, children) => {
+          child = self.try_resolve(epoch, children[BTreeNode::child_index(&keys, key)]);
+          parent = Some((page, version));
+        }
+      }
+    }
+  }
+
+  // None when a node changed before it could be latched, nothing was written then
+  fn try_insert_optimistic(&self, epoch: &EpochGuard, key: u64, value: &[u8]) -> Result<Option<Option<Vec<u8>>>> {
+    let Some(mut path) = self.try_descend(epoch, key)? else {
+      return Ok(None)
+    };
+
+    let (mut page, mut node, mut version) = path.pop().ok_or_else(|| anyhow!("B+Tree {} has no root", self.root()))?;
+    let BTreeNode::Leaf(entries, _) = &mut node else {
+      return Err(anyhow!("Page {} is not a B+Tree leaf", page.pid()))
+    };
+
+    let replaced = match entries.binary_search_by_key(&key, |(key, _)| *key) {
+      Ok(idx) => Some(std::mem::replace(&mut entries[idx].1, value.to_vec())),
+      Err(idx) => {
+        entries.insert(idx, (key, value.to_vec()));
+        None
+      }
+    };
+
+    // Splits go up the path, new nodes are written as they're split off since nothing points at them yet
+    let (mut writes, mut fresh) = (vec![], vec![]);
+    let mut split: Split = None;
+
+    loop {
+      if let (Some((separator, right)), BTreeNode::Inner(keys, children)) = (split.take(), &mut node) {
+        let idx = BTreeNode::child_index(keys, key);
+        keys.insert(idx, separator);
+        children.insert(idx + 1, right);
+      }
+
+      if node.len() > BTREE_MAX_NODE_LEN {
+        let (separator, right) = node.split();
+        let mut right_page = self.pages().try_alloc_in(right.len() as u32, right.tier())?;
+        if let BTreeNode::Leaf(_, next) = &mut node {
+          *next = right_page.pid();
+        }
+
+        right.try_write(&mut right_page.try_write()?)?;
+        split = Some((separator, (right_page.pid(), right_page.page().addr())));
+        fresh.push(right_page);
+      }
+
+      writes.push((page, node, version));
+
+      match (path.pop(), split) {
+        (Some(parent), Some(_)) => (page, node, version) = parent,
+
+        // The root's halves move into new children and the root points at both
+        (None, Some((separator, right))) => {
+          let (root, left, version) = writes.pop().ok_or_else(|| anyhow!("B+Tree {} lost its root", self.root()))?;
+          let mut left_page = self.pages().try_alloc_in(left.len() as u32, left.tier())?;
+          left.try_write(&mut left_page.try_write()?)?;
+
+          let children = vec![(left_page.pid(), left_page.page().addr()), right];
+          writes.push((root, BTreeNode::Inner(vec![separator], children), version));
+          fresh.push(left_page);
+          break
+        }
+
+        _ => break
+      }
+    }
+
+    // Parents first so the nodes that split still hold everything until they shrink
+    writes.reverse();
+
+    let (pages, nodes): (Vec<_>, Vec<_>) = writes.iter_mut().map(|(page, node, version)| ((page, *version), node)).unzip();
+    let Some(latched) = Self::try_latch(pages) else {
+      for page in fresh {
+        self.pages().try_free(page)?;
+      }
+
+      return Ok(None)
+    };
+
+    self.try_write_nodes(latched.into_iter().zip(nodes).collect())?;
+    Ok(Some(replaced))
+  }
+
+  // None when a node changed before it could be latched, nothing was written then
+  fn try_remove_optimistic(&self, epoch: &EpochGuard, key: u64) -> Result<Option<Option<Vec<u8>>>> {
+    let Some(mut path) = self.try_descend(epoch, key)? else {
+      return Ok(None)
+    };
+
+    let (mut page, mut node, mut version) = path.pop().ok_or_else(|| anyhow!("B+Tree {} has no root", self.root()))?;
+    let BTreeNode::Leaf(entries, _) = &mut node else {
+      return Err(anyhow!("Page {} is not a B+Tree leaf", page.pid()))
+    };
+
+    let removed = match entries.binary_search_by_key(&key, |(key, _)| *key) {
+      Ok(idx) => entries.remove(idx).1,
+      Err(_) => return Ok(Some(None))
+    };
+
+    // Merges go up the path, the merged node is written before the parent lets go of its sibling
+    let (mut writes, mut frees): (Vec<Level>, Vec<(PageGuard, usize)>) = (vec![], vec![]);
+
+    loop {
+      let (parent, mut parent_node, parent_version) = match path.pop() {
+        Some(parent) if node.len() < BTREE_MERGE_LEN => parent,
+        Some(_) => {
+          writes.push((page, node, version));
+          break
+        }
+
+        // A root down to a single child takes over what was just merged into it
+        None => {
+          match (&node, writes.pop()) {
+            (BTreeNode::Inner(keys, _), Some((child, child_node, child_version))) if keys.is_empty() => {
+              writes.push((page, child_node, version));
+              frees.push((child, child_version));
+            }
+
+            (_, merged) => writes.extend(merged.into_iter().chain([(page, node, version)]))
+          }
+
+          break
+        }
+      };
+
+      let BTreeNode::Inner(keys, children) = &mut parent_node else {
+        return Err(anyhow!("Page {} is not an inner B+Tree node", parent.pid()))
+      };
+
+      if keys.is_empty() {
+        writes.push((page, node, version));
+        break
+      }
+
+      let idx = BTreeNode::child_index(keys, key);
+      let left = idx.min(keys.len() - 1);
+      let sibling = self.try_load(epoch, children[if left == idx { idx + 1 } else { left }].0);
+      if !Self::is_unchanged(parent.page(), parent_version) {
+        return Ok(None)
+      }
+
+      let Some(sibling) = sibling? else {
+        return Ok(None)
+      };
+
+      if node.merged_len(&sibling.1) > BTREE_MAX_NODE_LEN {
+        writes.push((page, node, version));
+        break
+      }
+
+      let ((left_page, mut merged, left_version), (right_page, right, right_version)) = if left == idx {
+        ((page, node, version), sibling)
+      } else {
+        (sibling, (page, node, version))
+      };
+
+      merged.merge(keys[left], right)?;
+      keys.remove(left);
+      children.remove(left + 1);
+
+      writes.push((left_page, merged, left_version));
+      frees.push((right_page, right_version));
+      (page, node, version) = (parent, parent_node, parent_version);
+    }
+
+    let (pages, nodes): (Vec<_>, Vec<_>) = writes.iter_mut().map(|(page, node, version)| ((page, *version), node)).unzip();
+    let latched = Self::try_latch(pages);
+    let freed = Self::try_latch(frees.iter_mut().map(|(page, version)| (page, *version)));
+
+    let (Some(latched), Some(freed)) = (latched, freed) else {
+      return Ok(None)
+    };
+
+    self.try_write_nodes(latched.into_iter().zip(nodes).collect())?;
+
+    // A lookup that read the parent before it let go of a freed node fails to fetch its PID or finds another page, either way the parent changed and it restarts
+    for page in freed {
+      self.pages().try_free_latched(page)?;
+    }
+
+    Ok(Some(Some(removed)))
+  }
+
+  //
+  // The nodes from the root down to the leaf that holds the key, None when a
+  //  parent changed while its child was read. A parent whose child moved to
+  //  another frame since it was written is pointed at the new one in place
+  //  when its latch is free
+  //
+  fn try_descend(&self, epoch: &EpochGuard, key: u64) -> Result<Option<Vec<Level<'a>>>> {
+    let mut path = match self.try_load(epoch, self.root())? {
+      Some(root) => vec![root],
+      None => return Ok(None)
+    };
+
+    while let Some((page, node, version)) = path.last_mut() {
+      let BTreeNode::Inner(keys, children) = node else {
+        break
+      };
+
+      let idx = BTreeNode::child_index(keys, key);
+      let child = self.try_load(epoch, children[idx].0);
+      if !Self::is_unchanged(page.page(), *version) {
+        return Ok(None)
+      }
+
+      let Some(child) = child? else {
+        return Ok(None)
+      };
+
+      let addr = child.0.page().addr();
+      if children[idx].1 != addr {
+        if let Some(mut latched) = page.try_latch().filter(|latched| PageVLDS::version(latched.vlds().value()) == *version) {
+          children[idx].1 = addr;
+          node.try_write(&mut latched)?;
+
+          // Releasing the latch bumps the version once
+          drop(latched);
+          *version += 1;
+        }
+      }
+
+      path.push(child);
+    }
+
+    Ok(Some(path))
+  }
+
+  // A single optimistic read of the node, None when a writer got in the way
+  fn try_load(&self, epoch: &EpochGuard, pid: usize) -> Result<Option<Level<'a>>> {
+    let page = self.pages().try_fetch(pid)?;
+    let read = BTreeNode::try_read_optimistic(self.pages(), page.page(), epoch)?;
+
+    Ok(read.map(|(node, version)| (page, node, version)))
+  }
+
+  // Through the frame the parent last saw the child in while it still holds it, by PID otherwise
+  fn try_resolve<'e>(&self, epoch: &'e EpochGuard, (pid, addr): (usize, usize)) -> Result<NodePage<'e, 'a>> {
+    match self.pages().swizzled(Swip::swizzled(addr), pid, epoch) {
+      Some(page) => Ok(NodePage::Swizzled(page)),
+      None => Ok(NodePage::Fetched(self.pages().try_fetch(pid)?))
+    }
+  }
+
+  fn is_unchanged(page: &Page, version: usize) -> bool {
+    let value = page.vlds().value();
+    PageVLDS::version(value) == version && !PageVLDS::is_exclusive(PageVLDS::latch(value))
+  }
+
+  // Latches every page without waiting, None unless all of them are still at the version they were read at
+  fn try_latch<'p, I>(pages: I) -> Option<Vec<WriteGuard<'p, 'a>>>
+    where I: IntoIterator<Item = (&'p mut PageGuard<'a>, usize)>, 'a: 'p {
+    let mut latched = vec![];
+
+    for (page, version) in pages {
+      let page = page.try_latch()?;
+      if PageVLDS::version(page.vlds().value()) != version {
+        return None
+      }
+
+      latched.push(page);
+    }
+
+    Some(latched)
+  }
+
+  //
+  // Writes the nodes in order, pointing every parent at the frames its
+  //  children were written to. A parent written before its child moved is
+  //  re-swizzled in place once all of them are written
+  //
+  fn try_write_nodes(&self, mut latched: Vec<(WriteGuard<'_, 'a>, &mut BTreeNode)>) -> Result<()> {
+    let mut addrs = HashMap::new();
+
+    for (page, node) in latched.iter_mut() {
+      node.reswizzle(&addrs);
+      self.try_write_node(page, node)?;
+      addrs.insert(PageSWIP::pid(page.swip().value()), page.addr());
+    }
+
+    for (page, node) in latched.iter_mut() {
+      if node.reswizzle(&addrs) {
+        node.try_write(page)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  // Moves the page into the class that fits the node only when that changed, then writes what differs
+  fn try_write_node(&self, page: &mut WriteGuard<'_, 'a>, node: &BTreeNode) -> Result<()> {
+    if PageSWIP::cid(page.swip().value()) != page_class::to_fit(node.len() as u32)? {
+      self.pages().try_resize_latched(page, node.len() as u32)?;
+    }
+
+    node.try_write(page)?;
+    Ok(())
+  }
+
+  fn try_free_node(&self, pid: usize) -> Result<()> {
+    let page = self.pages().try_fetch(pid)?;
+
+    if let BTreeNode::Inner(_, children) = BTreeNode::try_read(self.pages(), &page)? {
+      for (child, _) in children {
+        self.try_free_node(child)?;
+      }
+    }
+
+    self.pages().try_free(page)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::{ PageIdPool, PageStore, PageTiers };
+
+  use std::{
+    sync::atomic::{ AtomicU64, Ordering },
+    thread
+  };
+
+  #[test]
+  fn test_insert_lookup_and_remove() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let mut worker = pages.epochs().register();
+    let tree = BTree::try_create(&pages)?;
+    let value = |key: u64| key.to_be_bytes().repeat(16);
+
+    // Spread out so inserts land all over the leaves
+    let keys = (0..4096u64).map(|key| key * 7919 % 4096).collect::<Vec<u64>>();
+    for key in keys.iter() {
+      assert_eq!(None, tree.try_insert(&mut worker, *key, &value(*key))?);
+    }
+
+    assert!(!BTreeNode::try_read(&pages, &pages.try_fetch(tree.root())?)?.is_leaf());
+    assert_eq!(Some(value(5)), tree.try_insert(&mut worker, 5, &[5])?);
+    assert_eq!(Some(vec![5]), tree.try_get(&mut worker, 5)?);
+    assert_eq!(Some(value(4095)), tree.try_get(&mut worker, 4095)?);
+    assert_eq!(None, tree.try_get(&mut worker, 4096)?);
+
+    for key in keys.iter().filter(|key| **key != 5) {
+      assert_eq!(Some(value(*key)), tree.try_remove(&mut worker, *key)?);
+    }
+
+    // Everything merged back into a root leaf in the smallest class
+    assert_eq!(None, tree.try_remove(&mut worker, 1)?);
+    assert_eq!(Some(vec![5]), tree.try_get(&mut worker, 5)?);
+    assert!(BTreeNode::try_read(&pages, &pages.try_fetch(tree.root())?)?.is_leaf());
+    pages.try_reclaim()?;
+    assert_eq!(4096, pages.used_bytes());
+
+    drop(worker);
+    tree.try_free()?;
+    assert_eq!(0, pages.used_bytes());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_children_are_swizzled_and_unchanged_parents_left_alone() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let mut worker = pages.epochs().register();
+    let tree = BTree::try_create(&pages)?;
+
+    for key in 0..1024u64 {
+      tree.try_insert(&mut worker, key, &key.to_be_bytes().repeat(16))?;
+    }
+
+    let root = pages.try_fetch(tree.root())?;
+    let BTreeNode::Inner(_, children) = BTreeNode::try_read(&pages, &root)? else {
+      panic!("the root split")
+    };
+
+    for (pid, addr) in children.iter() {
+      assert_eq!(*addr, pages.try_fetch(*pid)?.page().addr());
+    }
+
+    // A value of the same length only changes its leaf
+    let version = PageVLDS::version(root.vlds().value());
+    tree.try_insert(&mut worker, 7, &[7; 128])?;
+    assert_eq!(version, PageVLDS::version(root.vlds().value()));
+    assert_eq!(Some(vec![7; 128]), tree.try_get(&mut worker, 7)?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_inner_nodes_are_kept_in_the_index_tier() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-btree-tiers-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new()
+      .with(StorageTier::Index, PageStore::try_open(dir.join("index"))?)
+      .with(StorageTier::Local, PageStore::try_open(dir.join("local"))?);
+
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?;
+    let mut worker = pages.epochs().register();
+    let tree = BTree::try_create(&pages)?;
+
+    for key in 0..1024u64 {
+      tree.try_insert(&mut worker, key, &key.to_be_bytes().repeat(16))?;
+    }
+
+    let mut root = pages.try_fetch(tree.root())?;
+    let BTreeNode::Inner(_, children) = BTreeNode::try_read(&pages, &root)? else {
+      panic!("the root split")
+    };
+
+    // The root and the leaves under it each land in their own tier
+    let stored_in = |tier: StorageTier, pid: usize| pages.store(tier).and_then(|store| store.location(pid)).is_some();
+    pages.try_flush(&mut root)?;
+    assert!(stored_in(StorageTier::Index, tree.root()));
+    assert!(!stored_in(StorageTier::Local, tree.root()));
+
+    for (pid, _) in children.iter() {
+      pages.try_flush(&mut pages.try_fetch(*pid)?)?;
+      assert!(stored_in(StorageTier::Local, *pid));
+      assert!(!stored_in(StorageTier::Index, *pid));
+    }
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_lookups_during_splits() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let tree = BTree::try_create(&pages)?;
+    let inserted = AtomicU64::new(0);
+
+    thread::scope(|scope| {
+      let readers = (0..4).map(|_| scope.spawn(|| -> Result<()> {
+        let mut worker = pages.epochs().register();
+
+        while inserted.load(Ordering::Acquire) < 4096 {
+          let key = inserted.load(Ordering::Acquire).saturating_sub(1) / 2;
+          if inserted.load(Ordering::Acquire) > 0 {
+            assert_eq!(Some(key.to_be_bytes().repeat(16)), tree.try_get(&mut worker, key)?);
+          }
+        }
+
+        Ok(())
+      })).collect::<Vec<_>>();
+
+      let mut worker = pages.epochs().register();
+      for key in 0..4096u64 {
+        tree.try_insert(&mut worker, key, &key.to_be_bytes().repeat(16))?;
+        inserted.store(key + 1, Ordering::Release);
+      }
+
+      readers.into_iter().try_for_each(|reader| reader.join().expect("reader panicked"))
+    })
+  }
+
+  #[test]
+  fn test_concurrent_writers() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let tree = BTree::try_create(&pages)?;
+    let value = |key: u64| key.to_be_bytes().repeat(16);
+
+    // Each writer inserts every fourth key and removes half of what it inserted
+    thread::scope(|scope| {
+      let writers = (0..4u64).map(|writer| {
+        let tree = &tree;
+        let pages = &pages;
+
+        scope.spawn(move || -> Result<()> {
+          let mut worker = pages.epochs().register();
+          let keys = (0..1024u64).map(|idx| idx * 4 + writer).collect::<Vec<u64>>();
+
+          for key in keys.iter() {
+            assert_eq!(None, tree.try_insert(&mut worker, *key, &value(*key))?);
+          }
+
+          for key in keys.iter().filter(|key| *key % 8 < 4) {
+            assert_eq!(Some(value(*key)), tree.try_remove(&mut worker, *key)?);
+          }
+
+          Ok(())
+        })
+      }).collect::<Vec<_>>();
+
+      writers.into_iter().try_for_each(|writer| writer.join().expect("writer panicked"))
+    })?;
+
+    let mut worker = pages.epochs().register();
+    for key in 0..4096u64 {
+      let expected = (key % 8 >= 4).then(|| value(key));
+      assert_eq!(expected, tree.try_get(&mut worker, key)?);
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,330 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::{
+  collections::HashMap,
+  hint::spin_loop,
+  io::Cursor,
+  ops::Range,
+  thread
+};
+
+use crate::{ HEADER_LEN, EpochGuard, Page, PageGuard, PageManager, PageSWIP, StorageTier, WriteGuard };
+
+// Kind, encoded length and the next leaf's PID ahead of the entries
+pub const BTREE_NODE_HEADER_LEN: usize = 16;
+
+// Key and value length ahead of each value in a leaf
+pub const BTREE_LEAF_ENTRY_LEN: usize = 12;
+
+// PID and frame address of each child of an inner node
+pub const BTREE_CHILD_LEN: usize = 16;
+
+const LEAF: u32 = 0;
+const INNER: u32 = 1;
+
+//
+// Nodes are decoded from their page, changed and only the bytes that differ
+//  are written back. All integers are stored big endian:
+//
+//  Leaf:  [kind][len][next] ([key][len][value])*
+//  Inner: [kind][len][0] [pid][addr] ([key][pid][addr])*
+//
+// The address is the frame a child was in when its parent was last written,
+//  readers go straight to it while it still holds the child (see
+//  PageManager::swizzled) and fall back to the PID otherwise
+//
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BTreeNode {
+  // Entries in key order and the PID of the next leaf, 0 for the last one
+  Leaf(Vec<(u64, Vec<u8>)>, usize),
+
+  // Separator keys and the children around them as (PID, frame address), child i holds the keys below separator i
+  Inner(Vec<u64>, Vec<(usize, usize)>)
+}
+
+impl BTreeNode {
+  pub fn is_leaf(&self) -> bool {
+    matches!(self, Self::Leaf(..))
+  }
+
+  // Inner nodes are read on every lookup so they go to the index tier
+  pub fn tier(&self) -> StorageTier {
+    if self.is_leaf() { StorageTier::default() } else { StorageTier::Index }
+  }
+
+  pub fn count(&self) -> usize {
+    match self {
+      Self::Leaf(entries, _) => entries.len(),
+      Self::Inner(keys, _) => keys.len()
+    }
+  }
+
+  // Encoded length in bytes
+  pub fn len(&self) -> usize {
+    match self {
+      Self::Leaf(entries, _) => BTREE_NODE_HEADER_LEN + entries.iter().map(|(_, value)| BTREE_LEAF_ENTRY_LEN + value.len()).sum::<usize>(),
+      Self::Inner(keys, _) => BTREE_NODE_HEADER_LEN + BTREE_CHILD_LEN + keys.len() * (8 + BTREE_CHILD_LEN)
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.count() == 0
+  }
+
+  // The child that holds the key
+  pub fn child_index(keys: &[u64], key: u64) -> usize {
+    keys.partition_point(|separator| *separator <= key)
+  }
+
+  // Points the children at the frames they were written to, true when any of them moved
+  pub fn reswizzle(&mut self, addrs: &HashMap<usize, usize>) -> bool {
+    let Self::Inner(_, children) = self else {
+      return false
+    };
+
+    let mut moved = false;
+    for (pid, addr) in children.iter_mut() {
+      if let Some(frame) = addrs.get(pid).filter(|frame| *frame != addr) {
+        *addr = *frame;
+        moved = true;
+      }
+    }
+
+    moved
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(self.len());
+
+    match self {
+      Self::Leaf(entries, next) => {
+        bytes.extend_from_slice(&LEAF.to_be_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(*next as u64).to_be_bytes());
+
+        for (key, value) in entries {
+          bytes.extend_from_slice(&key.to_be_bytes());
+          bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+          bytes.extend_from_slice(value);
+        }
+      }
+
+      Self::Inner(keys, children) => {
+        bytes.extend_from_slice(&INNER.to_be_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&0u64.to_be_bytes());
+        bytes.extend_from_slice(&(children[0].0 as u64).to_be_bytes());
+        bytes.extend_from_slice(&(children[0].1 as u64).to_be_bytes());
+
+        for (key, (pid, addr)) in keys.iter().zip(&children[1..]) {
+          bytes.extend_from_slice(&key.to_be_bytes());
+          bytes.extend_from_slice(&(*pid as u64).to_be_bytes());
+          bytes.extend_from_slice(&(*addr as u64).to_be_bytes());
+        }
+      }
+    }
+
+    bytes
+  }
+
+  //
+  // Splits off the upper half by encoded length and returns it with its
+  //  lowest key, the key moves up into the parent from an inner node. A
+  //  split leaf is left pointing at the next leaf, which is up to the caller
+  //
+  pub fn split(&mut self) -> (u64, Self) {
+    let half = self.len() / 2;
+
+    match self {
+      Self::Leaf(entries, next) => {
+        let mut len = BTREE_NODE_HEADER_LEN;
+        let at = entries.iter().position(|(_, value)| {
+          len += BTREE_LEAF_ENTRY_LEN + value.len();
+          len > half
+        }).unwrap_or(entries.len());
+
+        let right = entries.split_off(at.clamp(1, entries.len() - 1));
+        (right[0].0, Self::Leaf(right, *next))
+      }
+
+      Self::Inner(keys, children) => {
+        let at = keys.len() / 2;
+        let right_keys = keys.split_off(at + 1);
+        let separator = keys.pop().unwrap_or_default();
+        let right_children = children.split_off(at + 1);
+
+        (separator, Self::Inner(right_keys, right_children))
+      }
+    }
+  }
+
+  // Encoded length of the node merged with its right sibling
+  pub fn merged_len(&self, right: &Self) -> usize {
+    // Inner nodes take the separator key down from the parent
+    let separator = if self.is_leaf() { 0 } else { 8 };
+    self.len() + right.len() - BTREE_NODE_HEADER_LEN + separator
+  }
+
+  // Appends the right sibling, the separator between them comes down from the parent into an inner node
+  pub fn merge(&mut self, separator: u64, right: Self) -> Result<()> {
+    match (self, right) {
+      (Self::Leaf(entries, next), Self::Leaf(right, right_next)) => {
+        entries.extend(right);
+        *next = right_next;
+      }
+
+      (Self::Inner(keys, children), Self::Inner(right_keys, right_children)) => {
+        keys.push(separator);
+        keys.extend(right_keys);
+        children.extend(right_children);
+      }
+
+      _ => return Err(anyhow!("A leaf and an inner node can't be merged"))
+    }
+
+    Ok(())
+  }
+
+  // Copies the node out the same way lookups do, without keeping writers or other readers out
+  pub fn try_read(pages: &PageManager, page: &PageGuard) -> Result<Self> {
+    let mut worker = pages.epochs().register();
+
+    loop {
+      if let Some((node, _)) = Self::try_read_optimistic(pages, page.page(), &worker.enter())? {
+        return Ok(node)
+      }
+
+      // The frame stays latched once the page is taken out of it, that never ends
+      if PageSWIP::is_cleared(page.page().swip().value()) {
+        return Err(anyhow!("Page {} was taken out of its frame", page.pid()))
+      }
+
+      spin_loop();
+      thread::yield_now();
+    }
+  }
+
+  // Copies the node out without a latch, None when a writer got in the way, otherwise the node and its version
+  pub fn try_read_optimistic(pages: &PageManager, page: &Page, epoch: &EpochGuard) -> Result<Option<(Self, usize)>> {
+    let read = match pages.optimistic(page, epoch) {
+      Some(read) => read,
+      None => return Ok(None)
+    };
+
+    let pid = PageSWIP::pid(read.swip().value());
+    let mut header = vec![];
+    if read.try_read(0, BTREE_NODE_HEADER_LEN, &mut header)?.is_none() {
+      return Ok(None)
+    }
+
+    let len = match Self::try_len(pid, page.len() - HEADER_LEN, &header) {
+      Ok(len) => len,
+      Err(_) if !read.is_valid() => return Ok(None),
+      Err(err) => return Err(err)
+    };
+
+    let mut bytes = vec![];
+    if read.try_read(0, len, &mut bytes)?.is_none() {
+      return Ok(None)
+    }
+
+    Ok(Some((Self::try_from_bytes(pid, &bytes)?, read.version())))
+  }
+
+  // Writes the header and the bytes past it that differ from what the page holds, returns how many were written
+  pub fn try_write(&self, page: &mut WriteGuard) -> Result<usize> {
+    let bytes = self.to_bytes();
+    let current = page.bytes().get(HEADER_LEN..HEADER_LEN + bytes.len())
+      .ok_or_else(|| anyhow!("A {} byte node doesn't fit page {}", bytes.len(), PageSWIP::pid(page.swip().value())))?;
+    let ranges = Self::changed(&bytes, current);
+
+    for range in ranges.iter() {
+      page.write(range.start, range.len(), &mut Cursor::new(&bytes[range.clone()]))?;
+    }
+
+    Ok(ranges.iter().map(Range::len).sum())
+  }
+
+  pub fn try_from_bytes(pid: usize, bytes: &[u8]) -> Result<Self> {
+    let corrupt = || anyhow!("Page {} is not a B+Tree node", pid);
+    let u32_at = |at: usize| -> Result<u32> { Ok(u32::from_be_bytes(bytes.get(at..at + 4).ok_or_else(corrupt)?.try_into()?)) };
+    let u64_at = |at: usize| -> Result<u64> { Ok(u64::from_be_bytes(bytes.get(at..at + 8).ok_or_else(corrupt)?.try_into()?)) };
+
+    let len = Self::try_len(pid, bytes.len(), bytes)?;
+    let mut at = BTREE_NODE_HEADER_LEN;
+
+    match u32_at(0)? {
+      LEAF => {
+        let mut entries = vec![];
+
+        while at < len {
+          let (key, len) = (u64_at(at)?, u32_at(at + 8)? as usize);
+          let value = bytes.get(at + BTREE_LEAF_ENTRY_LEN..at + BTREE_LEAF_ENTRY_LEN + len).ok_or_else(corrupt)?;
+
+          entries.push((key, value.to_vec()));
+          at += BTREE_LEAF_ENTRY_LEN + len;
+        }
+
+        Ok(Self::Leaf(entries, u64_at(8)? as usize))
+      }
+
+      INNER => {
+        let count = (len - BTREE_NODE_HEADER_LEN).saturating_sub(BTREE_CHILD_LEN) / (8 + BTREE_CHILD_LEN);
+        let mut keys = Vec::with_capacity(count);
+        let mut children = vec![(u64_at(at)? as usize, u64_at(at + 8)? as usize)];
+        at += BTREE_CHILD_LEN;
+
+        for _ in 0..count {
+          keys.push(u64_at(at)?);
+          children.push((u64_at(at + 8)? as usize, u64_at(at + 16)? as usize));
+          at += 8 + BTREE_CHILD_LEN;
+        }
+
+        Ok(Self::Inner(keys, children))
+      }
+
+      _ => Err(corrupt())
+    }
+  }
+
+  // The header when it changed and the span from the first to the last changed byte past it
+  fn changed(bytes: &[u8], current: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    if bytes[..BTREE_NODE_HEADER_LEN] != current[..BTREE_NODE_HEADER_LEN] {
+      ranges.push(0..BTREE_NODE_HEADER_LEN);
+    }
+
+    let (body, old) = (&bytes[BTREE_NODE_HEADER_LEN..], &current[BTREE_NODE_HEADER_LEN..]);
+    if let Some(range) = Self::difference(body, old) {
+      ranges.push(BTREE_NODE_HEADER_LEN + range.start..BTREE_NODE_HEADER_LEN + range.end);
+    }
+
+    ranges
+  }
+
+  // Bytes from the first to the last that differ, compared a chunk at a time before looking for the byte
+  fn difference(bytes: &[u8], other: &[u8]) -> Option<Range<usize>> {
+    let differs = |(new, old): (&u8, &u8)| new != old;
+
+    let first = bytes.chunks(64).zip(other.chunks(64)).position(|(new, old)| new != old)? * 64;
+    let first = first + bytes[first..].iter().zip(&other[first..]).position(differs)?;
+
+    let last = bytes.len() - bytes.rchunks(64).zip(other.rchunks(64)).position(|(new, old)| new != old)? * 64;
+    let last = bytes[..last].iter().zip(&other[..last]).rposition(differs)?;
+
+    Some(first..last + 1)
+  }
+
+  // The encoded length from the node's header
+  fn try_len(pid: usize, data_len: usize, header: &[u8]) -> Result<usize> {
+    let len = header.get(4..8).map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize);
+
+    match len {
+      Some(len) if (BTREE_NODE_HEADER_LEN..=data_len).contains(&len) => Ok(len),
+      _ => Err(anyhow!("Page {} is not a B+Tree node", pid))
+    }
+  }
+}
@@ -0,0 +1,248 @@
+mod lease;
+mod manifest;
+
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::Mutex;
+
+use std::{
+  fs,
+  path::{ Path, PathBuf },
+  sync::Arc,
+  thread::JoinHandle,
+  time::Duration
+};
+
+use crate::{
+  PageFence, PageIdPool, PageManager, PageStore, PageTiers, StorageTier, Wal
+};
+
+pub use lease::*;
+pub use manifest::*;
+
+//
+// A database is stored as a directory on the SSP (see docs/cluster.md)
+//
+// <database>/
+//   MANIFEST                     current manifest, replaced by atomic rename
+//   LEASE                        owner, epoch and expiry of the current lease
+//   page_ids                     page id log for the database
+//   pages/page_table             page table of the shared tier
+//   pages/class-<cid>/extent-<n> page files per size class
+//   wal/segment-<n>              write-ahead log segments
+//
+
+pub const MANIFEST_FILE: &str = "MANIFEST";
+pub const PAGE_IDS_FILE: &str = "page_ids";
+pub const PAGES_DIR: &str = "pages";
+pub const WAL_DIR: &str = "wal";
+
+type Heartbeat = Mutex<Option<JoinHandle<Result<()>>>>;
+
+#[derive(Debug)]
+pub struct Database(PathBuf, Mutex<Manifest>, Arc<Lease>, Heartbeat);
+
+impl Database {
+  pub fn dir(&self) -> &Path {
+    &self.0
+  }
+
+  pub fn manifest(&self) -> Manifest {
+    self.1.lock().clone()
+  }
+
+  pub fn lease(&self) -> &Arc<Lease> {
+    &self.2
+  }
+
+  // Ok while the lease heartbeat runs, otherwise the error it stopped with
+  pub fn try_heartbeat(&self) -> Result<()> {
+    let mut heartbeat = self.3.lock();
+
+    match heartbeat.take() {
+      Some(handle) if !handle.is_finished() => {
+        *heartbeat = Some(handle);
+        Ok(())
+      }
+
+      Some(handle) => {
+        handle.join().map_err(|_| anyhow!("Lease heartbeat panicked"))??;
+        Err(anyhow!("Lease heartbeat stopped after the lease was released"))
+      }
+
+      None => Err(anyhow!("Lease heartbeat has stopped"))
+    }
+  }
+
+  pub fn manifest_path(&self) -> PathBuf {
+    self.dir().join(MANIFEST_FILE)
+  }
+
+  pub fn page_ids_path(&self) -> PathBuf {
+    self.dir().join(PAGE_IDS_FILE)
+  }
+
+  pub fn pages_dir(&self) -> PathBuf {
+    self.dir().join(PAGES_DIR)
+  }
+
+  pub fn wal_dir(&self) -> PathBuf {
+    self.dir().join(WAL_DIR)
+  }
+
+  pub fn wal_segment_path(&self, segment: usize) -> PathBuf {
+    Wal::segment_path_in(&self.wal_dir(), segment)
+  }
+
+  pub fn try_wal_segments(&self) -> Result<Vec<usize>> {
+    Wal::try_segments_in(&self.wal_dir())
+  }
+
+  pub fn try_wal(&self) -> Result<Wal> {
+    Wal::try_open(self.wal_dir())
+  }
+
+  pub fn try_set_catalog_root(&self, pid: usize) -> Result<Manifest> {
+    self.try_update(|manifest| manifest.with_catalog_root(Some(pid)))
+  }
+
+  // Fails if another process replaced the manifest since we last read or wrote it
+  pub fn try_update<F: FnOnce(&Manifest) -> Manifest>(&self, update: F) -> Result<Manifest> {
+    let mut manifest = self.1.lock();
+    self.try_heartbeat()?;
+    self.lease().try_check()?;
+    let on_disk = Manifest::try_read(&self.manifest_path())?;
+
+    if on_disk.version() != manifest.version() {
+      return Err(anyhow!("Manifest changed underneath us, expected version {} but found {}", manifest.version(), on_disk.version()))
+    }
+
+    let updated = update(&manifest).next_version();
+    updated.try_write(&self.manifest_path())?;
+    *manifest = updated.clone();
+
+    Ok(updated)
+  }
+
+  // The database directory backs the shared tier, the other tiers are node local
+  pub fn try_page_manager(&self, pool_size: usize, tiers: PageTiers) -> Result<PageManager> {
+    let page_ids = PageIdPool::try_open(self.page_ids_path())?;
+    let shared = PageStore::try_open(self.pages_dir())?.with_fence(self.lease().clone());
+    let tiers = tiers.with(StorageTier::Shared, shared);
+    PageManager::try_new_with(pool_size, page_ids, tiers)
+  }
+
+  pub fn try_create<P: AsRef<Path>>(dir: P) -> Result<Self> {
+    Self::try_create_as(dir, &default_owner(), DEFAULT_LEASE_DURATION)
+  }
+
+  pub fn try_create_as<P: AsRef<Path>>(dir: P, owner: &str, duration: Duration) -> Result<Self> {
+    let dir = dir.as_ref().to_path_buf();
+    let manifest_path = dir.join(MANIFEST_FILE);
+
+    if manifest_path.exists() {
+      return Err(anyhow!("Database already exists at {}", dir.display()))
+    }
+
+    fs::create_dir_all(dir.join(PAGES_DIR))?;
+    fs::create_dir_all(dir.join(WAL_DIR))?;
+    let (lease, heartbeat) = Self::try_lease(&dir, owner, duration)?;
+
+    // The manifest goes last so a half created directory is never opened
+    let manifest = Manifest::new();
+    manifest.try_write(&manifest_path)?;
+
+    Ok(Self(dir, Mutex::new(manifest), lease, Mutex::new(Some(heartbeat))))
+  }
+
+  pub fn try_open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+    Self::try_open_as(dir, &default_owner(), DEFAULT_LEASE_DURATION)
+  }
+
+  pub fn try_open_as<P: AsRef<Path>>(dir: P, owner: &str, duration: Duration) -> Result<Self> {
+    let dir = dir.as_ref().to_path_buf();
+    let manifest_path = dir.join(MANIFEST_FILE);
+
+    if !manifest_path.exists() {
+      return Err(anyhow!("No database found at {}", dir.display()))
+    }
+
+    // Take the lease first so nobody replaces the manifest while we read it
+    let (lease, heartbeat) = Self::try_lease(&dir, owner, duration)?;
+    let manifest = Manifest::try_read(&manifest_path)?;
+
+    Ok(Self(dir, Mutex::new(manifest), lease, Mutex::new(Some(heartbeat))))
+  }
+
+  // Private Helpers
+
+  fn try_lease(dir: &Path, owner: &str, duration: Duration) -> Result<(Arc<Lease>, JoinHandle<Result<()>>)> {
+    let lease = Arc::new(Lease::try_acquire(dir, owner, duration)?);
+    let heartbeat = lease.spawn_heartbeat();
+    Ok((lease, heartbeat))
+  }
+}
+
+fn default_owner() -> String {
+  let host = std::env::var("HOSTNAME").unwrap_or_else(|_| String::from("localhost"));
+  format!("{}-{}", host, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vex-database-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+  }
+
+  #[test]
+  fn test_create_and_open() -> Result<()> {
+    let dir = temp_dir("create");
+    assert!(Database::try_open(&dir).is_err());
+
+    let database = Database::try_create(&dir)?;
+    assert_eq!(Manifest::new(), database.manifest());
+    assert!(Database::try_create(&dir).is_err());
+
+    let manifest = database.try_set_catalog_root(7)?;
+    assert_eq!((2, Some(7)), (manifest.version(), manifest.catalog_root()));
+
+    // The rename leaves nothing behind but the manifest itself
+    assert!(!database.manifest_path().with_extension("tmp").exists());
+    drop(database);
+
+    let database = Database::try_open(&dir)?;
+    assert_eq!(manifest, database.manifest());
+    assert!(database.pages_dir().is_dir() && database.wal_dir().is_dir());
+    drop(database);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_update_fails_on_a_replaced_manifest() -> Result<()> {
+    let dir = temp_dir("update");
+    let database = Database::try_create(&dir)?;
+
+    // Another writer bumps the version behind our back
+    let replaced = database.manifest().with_catalog_root(Some(3)).next_version();
+    replaced.try_write(&database.manifest_path())?;
+
+    assert!(database.try_set_catalog_root(5).is_err());
+    assert_eq!(replaced, Manifest::try_read(&database.manifest_path())?);
+    assert_eq!(1, database.manifest().version());
+
+    fs::write(database.manifest_path(), "format 2\nversion 3\ncatalog_root none\n")?;
+    assert!(Manifest::try_read(&database.manifest_path()).is_err());
+    drop(database);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,336 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::Mutex;
+
+use std::{
+  fs::{ self, File, OpenOptions },
+  io::{ ErrorKind, Write },
+  path::{ Path, PathBuf },
+  sync::{
+    Arc,
+    atomic::{ AtomicBool, AtomicU64, AtomicUsize, Ordering }
+  },
+  thread::{ self, JoinHandle },
+  time::{ Duration, SystemTime, UNIX_EPOCH }
+};
+
+use crate::PageFence;
+
+pub const LEASE_FILE: &str = "LEASE";
+pub const LEASE_LOCK_FILE: &str = "LEASE.lock";
+pub const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(10);
+
+//
+// A database can only be accessed by one pod at a time (see docs/cluster.md)
+//
+// The LEASE file names the owner, the epoch and when the lease expires. Every
+//  acquisition bumps the epoch which is the fencing token page writes are
+//  checked against: a write goes ahead only while the LEASE file still names
+//  us at our epoch, and the page store records the epoch in every slot so it
+//  refuses writes older than a page it has seen (see PageStore::try_fence).
+//  An owner that misses its heartbeats stops writing as soon as its local
+//  view of the lease expires. We assume clocks across the pod are within a
+//  small fraction of the lease duration of each other.
+//
+
+// Directory, owner, epoch, expiry, duration, released and why the last renewal failed
+#[derive(Debug)]
+pub struct Lease(PathBuf, String, AtomicUsize, AtomicU64, Duration, AtomicBool, Mutex<Option<String>>);
+
+impl Lease {
+  fn dir(&self) -> &Path {
+    &self.0
+  }
+
+  pub fn owner(&self) -> &str {
+    &self.1
+  }
+
+  pub fn epoch(&self) -> usize {
+    self.2.load(Ordering::Acquire)
+  }
+
+  pub fn expires_at(&self) -> u64 {
+    self.3.load(Ordering::Acquire)
+  }
+
+  pub fn duration(&self) -> Duration {
+    self.4
+  }
+
+  pub fn is_released(&self) -> bool {
+    self.5.load(Ordering::Acquire)
+  }
+
+  pub fn is_held(&self) -> bool {
+    !self.is_released() && now_millis() < self.expires_at()
+  }
+
+  // Error of the last failed heartbeat renewal, cleared by the next one that succeeds
+  pub fn heartbeat_failure(&self) -> Option<String> {
+    self.6.lock().clone()
+  }
+
+  pub fn try_renew(&self) -> Result<()> {
+    let _lock = LeaseLock::try_acquire(self.dir(), self.duration())?;
+    let record = LeaseRecord::try_read(&self.dir().join(LEASE_FILE))?;
+
+    if record.epoch() != self.epoch() || record.owner() != self.owner() {
+      self.5.store(true, Ordering::Release);
+      return Err(anyhow!("Lease on {} was taken over by {} at epoch {}", self.dir().display(), record.owner(), record.epoch()))
+    }
+
+    let expires_at = now_millis() + self.duration().as_millis() as u64;
+    LeaseRecord(self.epoch(), self.owner().to_string(), expires_at).try_write(&self.dir().join(LEASE_FILE))?;
+    self.3.store(expires_at, Ordering::Release);
+
+    Ok(())
+  }
+
+  // Expires the lease on disk so the next owner doesn't have to wait it out
+  pub fn try_release(&self) -> Result<()> {
+    if self.5.swap(true, Ordering::AcqRel) {
+      return Ok(())
+    }
+
+    let _lock = LeaseLock::try_acquire(self.dir(), self.duration())?;
+    let path = self.dir().join(LEASE_FILE);
+    let record = LeaseRecord::try_read(&path)?;
+
+    if record.epoch() == self.epoch() && record.owner() == self.owner() {
+      LeaseRecord(self.epoch(), self.owner().to_string(), 0).try_write(&path)?;
+    }
+
+    Ok(())
+  }
+
+  //
+  // Renews every third of the lease duration until the lease is released or
+  //  dropped. A failed renewal is retried while the lease is still held and
+  //  recorded for try_check to report, the heartbeat ends with the error once
+  //  the lease is lost
+  //
+  pub fn spawn_heartbeat(self: &Arc<Self>) -> JoinHandle<Result<()>> {
+    let lease = Arc::downgrade(self);
+    let interval = self.duration() / 3;
+
+    thread::spawn(move || {
+      loop {
+        thread::sleep(interval);
+
+        let lease = match lease.upgrade() {
+          Some(lease) if !lease.is_released() => lease,
+          _ => return Ok(())
+        };
+
+        match lease.try_renew() {
+          Ok(_) => *lease.6.lock() = None,
+          Err(err) => {
+            *lease.6.lock() = Some(err.to_string());
+
+            if !lease.is_held() {
+              return Err(err)
+            }
+          }
+        }
+      }
+    })
+  }
+
+  pub fn try_acquire<P: AsRef<Path>>(dir: P, owner: &str, duration: Duration) -> Result<Self> {
+    let dir = dir.as_ref().to_path_buf();
+    let path = dir.join(LEASE_FILE);
+    let _lock = LeaseLock::try_acquire(&dir, duration)?;
+
+    let epoch = if path.exists() {
+      let record = LeaseRecord::try_read(&path)?;
+
+      if record.owner() != owner && now_millis() < record.expires_at() {
+        return Err(anyhow!("Database at {} is leased by {} until {}", dir.display(), record.owner(), record.expires_at()))
+      }
+
+      record.epoch() + 1
+    } else {
+      1
+    };
+
+    let expires_at = now_millis() + duration.as_millis() as u64;
+    LeaseRecord(epoch, owner.to_string(), expires_at).try_write(&path)?;
+
+    Ok(Self(dir, owner.to_string(), AtomicUsize::new(epoch), AtomicU64::new(expires_at), duration, AtomicBool::new(false), Mutex::new(None)))
+  }
+}
+
+impl PageFence for Lease {
+  // Compares against the LEASE file, which is replaced by rename so it is read without the lease lock
+  fn try_check(&self) -> Result<usize> {
+    if !self.is_held() {
+      return match self.heartbeat_failure() {
+        Some(failure) => Err(anyhow!("Lease held by {} at epoch {} expired after the heartbeat failed: {}", self.owner(), self.epoch(), failure)),
+        None => Err(anyhow!("Lease held by {} at epoch {} is no longer valid", self.owner(), self.epoch()))
+      }
+    }
+
+    let record = LeaseRecord::try_read(&self.dir().join(LEASE_FILE))?;
+
+    if record.epoch() != self.epoch() || record.owner() != self.owner() {
+      self.5.store(true, Ordering::Release);
+      return Err(anyhow!("Lease on {} was taken over by {} at epoch {}", self.dir().display(), record.owner(), record.epoch()))
+    }
+
+    Ok(self.epoch())
+  }
+}
+
+impl Drop for Lease {
+  fn drop(&mut self) {
+    let _ = self.try_release();
+  }
+}
+
+// Epoch, owner and expiry as `key value` lines like the manifest
+#[derive(Debug)]
+struct LeaseRecord(usize, String, u64);
+
+impl LeaseRecord {
+  fn epoch(&self) -> usize {
+    self.0
+  }
+
+  fn owner(&self) -> &str {
+    &self.1
+  }
+
+  fn expires_at(&self) -> u64 {
+    self.2
+  }
+
+  fn try_read(path: &Path) -> Result<Self> {
+    let text = fs::read_to_string(path)?;
+    let (mut epoch, mut owner, mut expires_at) = (None, None, None);
+
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+      match line.split_once(' ') {
+        Some(("epoch", value)) => epoch = Some(value.parse::<usize>()?),
+        Some(("owner", value)) => owner = Some(value.to_string()),
+        Some(("expires_at", value)) => expires_at = Some(value.parse::<u64>()?),
+        _ => return Err(anyhow!("Unrecognized lease line: {}", line))
+      }
+    }
+
+    match (epoch, owner, expires_at) {
+      (Some(epoch), Some(owner), Some(expires_at)) => Ok(Self(epoch, owner, expires_at)),
+      _ => Err(anyhow!("Lease at {} is incomplete", path.display()))
+    }
+  }
+
+  fn try_write(&self, path: &Path) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      write!(tmp, "epoch {}\nowner {}\nexpires_at {}\n", self.epoch(), self.owner(), self.expires_at())?;
+      tmp.sync_all()?;
+    }
+
+    Ok(fs::rename(&tmp_path, path)?)
+  }
+}
+
+// Serializes lease updates between processes, removed on drop
+struct LeaseLock(PathBuf);
+
+impl LeaseLock {
+  fn try_acquire(dir: &Path, duration: Duration) -> Result<Self> {
+    let path = dir.join(LEASE_LOCK_FILE);
+
+    for _ in 0..100 {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(_) => return Ok(Self(path)),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+          // A lock older than the lease itself was left behind by a crashed process
+          if Self::is_stale(&path, duration) {
+            let _ = fs::remove_file(&path);
+          } else {
+            thread::sleep(Duration::from_millis(10));
+          }
+        }
+        Err(err) => return Err(err.into())
+      }
+    }
+
+    Err(anyhow!("Timed out waiting for lease lock at {}", path.display()))
+  }
+
+  fn is_stale(path: &Path, duration: Duration) -> bool {
+    match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+      Ok(modified) => modified.elapsed().is_ok_and(|age| age > duration),
+      Err(_) => false
+    }
+  }
+}
+
+impl Drop for LeaseLock {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.0);
+  }
+}
+
+fn now_millis() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vex-lease-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn test_takeover_fences_the_previous_owner() -> Result<()> {
+    let dir = temp_dir("takeover");
+    let first = Lease::try_acquire(&dir, "first", Duration::from_millis(50))?;
+    assert_eq!(1, first.try_check()?);
+    assert!(Lease::try_acquire(&dir, "second", Duration::from_secs(10)).is_err());
+
+    // The first owner stalls past its expiry and comes back after the takeover
+    thread::sleep(Duration::from_millis(80));
+    let second = Lease::try_acquire(&dir, "second", Duration::from_secs(10))?;
+    first.3.store(now_millis() + 10_000, Ordering::Release);
+
+    assert!(first.try_check().is_err());
+    assert!(first.is_released());
+    assert_eq!(2, second.try_check()?);
+
+    drop(first);
+    assert_eq!(2, second.try_check()?);
+    drop(second);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_heartbeat_reports_a_lost_lease() -> Result<()> {
+    let dir = temp_dir("heartbeat");
+    let lease = Arc::new(Lease::try_acquire(&dir, "first", Duration::from_millis(60))?);
+    let heartbeat = lease.spawn_heartbeat();
+
+    LeaseRecord(7, String::from("second"), now_millis() + 10_000).try_write(&dir.join(LEASE_FILE))?;
+
+    assert!(heartbeat.join().map_err(|_| anyhow!("Heartbeat panicked"))?.is_err());
+    assert!(lease.heartbeat_failure().is_some());
+    assert!(lease.try_check().is_err());
+
+    drop(lease);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,105 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::{
+  fs::{ self, File },
+  io::Write,
+  path::Path
+};
+
+pub const MANIFEST_FORMAT: usize = 1;
+
+//
+// The manifest is a small text file of `key value` lines
+//
+// format       - layout version of the database directory
+// version      - bumped on every manifest update
+// catalog_root - PID of the catalog root page or `none`
+//
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest(usize, Option<usize>);
+
+impl Manifest {
+  pub fn version(&self) -> usize {
+    self.0
+  }
+
+  pub fn catalog_root(&self) -> Option<usize> {
+    self.1
+  }
+
+  pub fn with_catalog_root(&self, pid: Option<usize>) -> Self {
+    Self(self.0, pid)
+  }
+
+  pub fn next_version(&self) -> Self {
+    Self(self.0 + 1, self.1)
+  }
+
+  pub fn new() -> Self {
+    Self(1, None)
+  }
+
+  pub fn to_text(&self) -> String {
+    let catalog_root = match self.catalog_root() {
+      Some(pid) => pid.to_string(),
+      None => String::from("none")
+    };
+
+    format!("format {}\nversion {}\ncatalog_root {}\n", MANIFEST_FORMAT, self.version(), catalog_root)
+  }
+
+  pub fn try_from_text(text: &str) -> Result<Self> {
+    let mut format = None;
+    let mut version = None;
+    let mut catalog_root = None;
+
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+      match line.split_once(' ') {
+        Some(("format", value)) => format = Some(value.parse::<usize>()?),
+        Some(("version", value)) => version = Some(value.parse::<usize>()?),
+        Some(("catalog_root", "none")) => catalog_root = None,
+        Some(("catalog_root", value)) => catalog_root = Some(value.parse::<usize>()?),
+        _ => return Err(anyhow!("Unrecognized manifest line: {}", line))
+      }
+    }
+
+    match (format, version) {
+      (Some(MANIFEST_FORMAT), Some(version)) => Ok(Self(version, catalog_root)),
+      (Some(format), _) if format != MANIFEST_FORMAT => Err(anyhow!("Unsupported manifest format {}", format)),
+      _ => Err(anyhow!("Manifest is missing its format or version"))
+    }
+  }
+
+  pub fn try_read(path: &Path) -> Result<Self> {
+    Self::try_from_text(&fs::read_to_string(path)?)
+  }
+
+  // Write + fsync a sibling file then rename it over the manifest so
+  //  readers only ever see the old or the new version
+  pub fn try_write(&self, path: &Path) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      tmp.write_all(self.to_text().as_bytes())?;
+      tmp.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, path)?;
+
+    if let Some(dir) = path.parent() {
+      File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Default for Manifest {
+  fn default() -> Self {
+    Self::new()
+  }
+}
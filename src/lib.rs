@@ -1,17 +1,28 @@
 #![feature(slice_ptr_get)]
 
+mod btree;
+mod database;
 mod page;
+mod page_blob;
 mod page_class;
 mod page_guard;
 mod page_manager;
+mod page_store;
+mod transaction_manager;
+mod wal;
 
+pub use btree::*;
+pub use database::*;
 pub use page::*;
+pub use page_blob::*;
 pub use page_class::*;
 pub use page_guard::*;
 pub use page_manager::*;
+pub use page_store::*;
+pub use transaction_manager::*;
+pub use wal::*;
 
-use std::mem::size_of;
-
-pub const SWIP_LEN: usize = size_of::<usize>();
-pub const VLDS_LEN: usize = size_of::<usize>();
-pub const HEADER_LEN: usize = SWIP_LEN + VLDS_LEN;
+pub const SWIP_LEN: usize = std::mem::size_of::<usize>();
+pub const VLDS_LEN: usize = std::mem::size_of::<usize>();
+pub const CHAIN_LEN: usize = 2 * std::mem::size_of::<usize>();
+pub const HEADER_LEN: usize = SWIP_LEN + VLDS_LEN + CHAIN_LEN;
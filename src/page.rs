@@ -1,3 +1,4 @@
+mod page_chain;
 mod page_data;
 mod page_swip;
 mod page_vlds;
@@ -9,9 +10,10 @@ use std::{
 };
 
 use crate::{
-  SWIP_LEN, VLDS_LEN, page_class
+  CHAIN_LEN, HEADER_LEN, SWIP_LEN, VLDS_LEN, page_class
 };
 
+pub use page_chain::*;
 pub use page_data::*;
 pub use page_swip::*;
 pub use page_vlds::*;
@@ -26,19 +28,35 @@ impl<'a> Page<'a> {
     self.0.len()
   }
 
-  pub fn swip(&'a self) -> PageSWIP<'a> {
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn bytes(&self) -> &[u8] {
+    self.0
+  }
+
+  pub fn addr(&self) -> usize {
+    self.0.as_ptr() as usize
+  }
+
+  pub fn swip(&self) -> PageSWIP<'_> {
     PageSWIP::from(Self::slice_swip(self.0))
   }
 
-  pub fn vlds(&'a self) -> PageVLDS<'a> {
+  pub fn vlds(&self) -> PageVLDS<'_> {
     PageVLDS::from(Self::slice_vlds(self.0))
   }
 
-  pub fn data(&'a self) -> PageData<&'a [u8]> {
+  pub fn chain(&self) -> PageChain<'_> {
+    PageChain::from(Self::slice_chain(self.0))
+  }
+
+  pub fn data(&self) -> PageData<&[u8]> {
     PageData::from(Self::slice_data(self.0, self.0.len()))
   }
 
-  pub fn data_mut(&'a mut self) -> PageData<&'a mut [u8]> {
+  pub fn data_mut(&mut self) -> PageData<&mut [u8]> {
     PageData::from(Self::slice_data_mut(self.0, self.0.len()))
   }
 }
@@ -49,17 +67,47 @@ impl<'a> Page<'a> {
   pub fn try_alloc(addr: usize, pid: usize, cid: usize) -> Result<Self> {
     let vlen = page_class::size_of(cid);
     let swip = PageSWIP::pack(pid, cid);
-    let vlds = PageVLDS::default_value();
 
     let slice = Self::slice_mut(addr, vlen);
+    let vlds = PageVLDS::succeeding(Self::frame_vlds(slice), PageVLDS::default_value());
+    Self::try_alloc_head(slice, swip, vlds)?;
+
+    // A new page has no past versions and is visible to everyone
+    let page = Self(slice);
+    page.chain().set_past(0);
+    page.chain().set_stamp(0);
+
+    Ok(page)
+  }
+
+  // A frame that already holds a page, such as one a prefetch faulted in
+  pub fn resident(addr: usize, cid: usize) -> Self {
+    Self(Self::slice_mut(addr, page_class::size_of(cid)))
+  }
+
+  // Fills the frame from a backing store and resets the header to an open, clean latch
+  pub fn try_fetch<F>(addr: usize, pid: usize, cid: usize, read: F) -> Result<Self>
+    where F: FnOnce(&mut [u8]) -> Result<()> {
+    let vlen = page_class::size_of(cid);
+    let swip = PageSWIP::pack(pid, cid);
+
+    let slice = Self::slice_mut(addr, vlen);
+    let vlds = PageVLDS::succeeding(Self::frame_vlds(slice), PageVLDS::clean_value());
+    read(slice)?;
     Self::try_alloc_head(slice, swip, vlds)?;
 
     Ok(Self(slice))
   }
 
+  // The VLDS the frame was left with by the page it held before
+  fn frame_vlds(slice: &[u8]) -> usize {
+    PageVLDS::from(Self::slice_vlds(slice)).value()
+  }
+
   fn try_alloc_head(slice: &mut [u8], swip: usize, vlds: usize) -> Result<usize> {
     let mut cursor = Cursor::new(slice);
-    Ok(cursor.write(&swip.to_be_bytes())? + cursor.write(&vlds.to_be_bytes())?)
+    // Native endian since both words are read back as atomics
+    Ok(cursor.write(&swip.to_ne_bytes())? + cursor.write(&vlds.to_ne_bytes())?)
   }
 
   //
@@ -71,15 +119,19 @@ impl<'a> Page<'a> {
   }
 
   fn slice_vlds(slice: &[u8]) -> &[u8] {
-    &slice[SWIP_LEN .. VLDS_LEN]
+    &slice[SWIP_LEN .. SWIP_LEN + VLDS_LEN]
+  }
+
+  fn slice_chain(slice: &[u8]) -> &[u8] {
+    &slice[SWIP_LEN + VLDS_LEN .. SWIP_LEN + VLDS_LEN + CHAIN_LEN]
   }
 
   fn slice_data(slice: &[u8], data_len: usize) -> &[u8] {
-    &slice[(SWIP_LEN + VLDS_LEN) .. data_len]
+    &slice[HEADER_LEN .. data_len]
   }
 
   fn slice_data_mut(slice: &mut [u8], data_len: usize) -> &mut [u8] {
-    &mut slice[(SWIP_LEN + VLDS_LEN) .. data_len]
+    &mut slice[HEADER_LEN .. data_len]
   }
 
   fn slice_mut(addr: usize, len: usize) -> &'a mut [u8] {
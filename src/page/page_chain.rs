@@ -0,0 +1,59 @@
+use std::{
+  sync::atomic::{
+    AtomicUsize, Ordering
+  }
+};
+
+// Stamps at or above this are ids of transactions that haven't committed yet
+pub const TXN_ID_BASE: usize = 1 << 63;
+
+//
+// Pages are versioned with copy-on-write (see docs/design.md), every page
+//  points to its newest past version and is stamped with the time it was
+//  committed at or the id of the transaction that is still writing it
+//
+// Versions are chained newest to oldest (see docs/storage/storage-logical.md)
+//  and the head keeps the PID everyone refers to the page by
+//
+
+#[derive(Debug)]
+pub struct PageChain<'a>(&'a AtomicUsize, &'a AtomicUsize);
+
+impl<'a> From<&'a [u8]> for PageChain<'a> {
+  fn from(slice: &'a [u8]) -> Self {
+    let (past, stamp) = slice.split_at(slice.len() / 2);
+    Self(Self::make_atomic_ref(past), Self::make_atomic_ref(stamp))
+  }
+}
+
+impl<'a> PageChain<'a> {
+  // PID of the newest past version, 0 when there is none
+  pub fn past(&self) -> usize {
+    self.0.load(Ordering::Acquire)
+  }
+
+  pub fn stamp(&self) -> usize {
+    self.1.load(Ordering::Acquire)
+  }
+
+  pub fn set_past(&self, pid: usize) {
+    self.0.store(pid, Ordering::Release)
+  }
+
+  pub fn set_stamp(&self, stamp: usize) {
+    self.1.store(stamp, Ordering::Release)
+  }
+
+  pub fn is_uncommitted(stamp: usize) -> bool {
+    stamp >= TXN_ID_BASE
+  }
+
+  // A transaction sees its own versions and everything committed up to its snapshot
+  pub fn is_visible(stamp: usize, snapshot: usize, txn: usize) -> bool {
+    stamp == txn || (!Self::is_uncommitted(stamp) && stamp <= snapshot)
+  }
+
+  fn make_atomic_ref(slice: &[u8]) -> &AtomicUsize {
+    unsafe { &*(slice as *const _ as *const AtomicUsize) }
+  }
+}
@@ -5,7 +5,7 @@ use std::{
 };
 
 pub const CID_BITS: usize = 0x0006;
-pub const CID_MASK: usize = 0x02FF;
+pub const CID_MASK: usize = 0x007E; // 0111_1110
 
 pub const TAG_BITS: usize = 0x0001;
 pub const TAG_MASK: usize = 0x0001;
@@ -33,13 +33,22 @@ impl<'a> From<&'a [u8]> for PageSWIP<'a> {
 
 impl<'a> PageSWIP<'a> {
   fn swip(&self) -> &AtomicUsize {
-    &self.0
+    self.0
   }
 
   pub fn value(&self) -> usize {
     self.swip().load(Ordering::Acquire)
   }
 
+  // A cleared tag says the frame no longer holds a page, only done under an exclusive latch
+  pub fn clear(&self) {
+    self.swip().store(0, Ordering::Release);
+  }
+
+  pub fn is_cleared(value: usize) -> bool {
+    value == 0
+  }
+
   pub fn tag(value: usize) -> usize {
     value & TAG_MASK
   }
@@ -61,7 +70,7 @@ impl<'a> PageSWIP<'a> {
   }
 
   fn pack_cid(value: usize, cid: usize) -> usize {
-    (value & !CID_MASK) | (cid & CID_MASK)
+    (value & !CID_MASK) | ((cid << TAG_BITS) & CID_MASK)
   }
 
   fn pack_pid(value: usize, pid: usize) -> usize {
@@ -76,3 +85,44 @@ impl<'a> PageSWIP<'a> {
     unsafe { &(*(atomic_ref as *const usize as *const AtomicUsize)) }
   }
 }
+
+//
+// A swip read out of a page, like a child reference in a B+Tree node. With
+//  the tag set it holds the PID and class of a page that may not be resident,
+//  cleared it holds the address of the frame the page is resident in
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Swip(usize);
+
+impl Swip {
+  pub fn value(&self) -> usize {
+    self.0
+  }
+
+  pub fn is_swizzled(&self) -> bool {
+    PageSWIP::tag(self.value()) == 0
+  }
+
+  pub fn pid(&self) -> Option<usize> {
+    if self.is_swizzled() { None } else { Some(PageSWIP::pid(self.value())) }
+  }
+
+  pub fn addr(&self) -> Option<usize> {
+    if self.is_swizzled() { Some(self.value()) } else { None }
+  }
+
+  pub fn unswizzled(pid: usize, cid: usize) -> Self {
+    Self(PageSWIP::pack(pid, cid))
+  }
+
+  // Frames are page aligned so the tag bit of an address is always clear
+  pub fn swizzled(addr: usize) -> Self {
+    Self(addr)
+  }
+}
+
+impl From<usize> for Swip {
+  fn from(value: usize) -> Self {
+    Self(value)
+  }
+}
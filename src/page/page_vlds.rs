@@ -1,6 +1,6 @@
 use std::sync::atomic::{ AtomicUsize, Ordering };
 
-/**
+/*
  * A versioned latch with 4
  */
 
@@ -37,6 +37,11 @@ impl<'a> PageVLDS<'a> {
     1usize
   }
 
+  // Version 0, Open Latch, Clean page
+  pub fn clean_value() -> usize {
+    0usize
+  }
+
   pub fn dirty(value: usize) -> usize {
     value & DIRTY_MASK
   }
@@ -61,6 +66,11 @@ impl<'a> PageVLDS<'a> {
     latch == 1
   }
 
+  // The value for the next page put in a frame, its version goes on from the last one so a reader of that never validates
+  pub fn succeeding(previous: usize, value: usize) -> usize {
+    Self::pack_version(value, Self::version(previous) + 1)
+  }
+
   // Private Helpers
 
   fn pack_dirty(value: usize, dirty: usize) -> usize {
@@ -68,7 +78,7 @@ impl<'a> PageVLDS<'a> {
   }
 
   fn pack_latch(value: usize, latch: usize) -> usize {
-    (value & !LATCH_MASK) | (latch & LATCH_MASK)
+    (value & !LATCH_MASK) | ((latch << DIRTY_BITS) & LATCH_MASK)
   }
 
   fn pack_version(value: usize, version: usize) -> usize {
@@ -95,20 +105,20 @@ impl<'a> PageVLDS<'a> {
 
   pub fn mark_clean(&self) -> Result<usize, usize> {
     let value = self.value();
-    let new_value = Self::pack_dirty(1, 0);
+    let new_value = Self::pack_dirty(value, 0);
     self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
   }
 
   pub fn mark_dirty(&self) -> Result<usize, usize> {
     let value = self.value();
-    let new_value = Self::pack_dirty(1, 1);
+    let new_value = Self::pack_dirty(value, 1);
     self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
   }
 
   pub fn latch_read(&self) -> Result<usize, usize> {
     let value = self.value();
     let latch = Self::latch(value);
-    let new_value = Self::pack_latch(1, latch + 1);
+    let new_value = Self::pack_latch(value, if Self::is_open(latch) { 2 } else { latch + 1 });
     self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
   }
 
@@ -118,12 +128,26 @@ impl<'a> PageVLDS<'a> {
     self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
   }
 
+  // Fails unless the latch is open, so two writers never both take it
   pub fn latch_write(&self) -> Result<usize, usize> {
     let value = self.value();
+    if !Self::is_open(Self::latch(value)) {
+      return Err(value)
+    }
+
     let new_value = Self::pack_latch(value, 1);
     self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
   }
 
+  // Bumps the version and opens the latch in one step so that optimistic
+  //  readers never see an open latch with a stale version
+  pub fn release_write(&self) -> Result<usize, usize> {
+    let value = self.value();
+    let version = Self::version(value);
+    let new_value = Self::pack_latch(Self::pack_version(value, version + 1), 0);
+    self.vlds().compare_exchange(value, new_value, Ordering::SeqCst, Ordering::Acquire)
+  }
+
   pub fn increment_version(&self) -> Result<usize, usize> {
     let value = self.value();
     let version = Self::version(value);
@@ -0,0 +1,279 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::io::{
+  self, Cursor, ErrorKind, Read, Seek, SeekFrom, Write
+};
+
+use crate::{
+  HEADER_LEN, MAX_CLASS_ID, AccessHint, PageGuard, PageManager
+};
+
+// Blob data is split into pages of this class
+pub const BLOB_CHUNK_CID: usize = 20;
+pub const BLOB_CHUNK_LEN: usize = (1 << BLOB_CHUNK_CID) - HEADER_LEN;
+
+// Length and chunk count ahead of the chunk PIDs
+pub const BLOB_DIRECTORY_LEN: usize = 16;
+
+// Chunk PIDs that fit in a directory page of the largest class
+pub const BLOB_MAX_CHUNKS: usize = ((1 << MAX_CLASS_ID) - HEADER_LEN - BLOB_DIRECTORY_LEN) / 8;
+
+//
+// Objects too large for a single page are stored as chunk pages listed by a
+//  directory page that holds the length of the blob and every chunk's PID.
+//  The directory grows with try_resize, which caps a blob at about 2^28
+//  chunks or 256TB
+//
+// Chunks are fetched as they are read or written with a Sequential hint so
+//  streaming a blob doesn't push hot pages out of memory
+//
+
+// Pages, directory PID, length, position, chunk PIDs and whether the directory is behind
+#[derive(Debug)]
+pub struct PageBlob<'a>(&'a PageManager, usize, u64, u64, Vec<usize>, bool);
+
+impl<'a> PageBlob<'a> {
+  fn pages(&self) -> &'a PageManager {
+    self.0
+  }
+
+  pub fn pid(&self) -> usize {
+    self.1
+  }
+
+  pub fn len(&self) -> u64 {
+    self.2
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn position(&self) -> u64 {
+    self.3
+  }
+
+  pub fn chunks(&self) -> &[usize] {
+    &self.4
+  }
+
+  // Writes the length and chunk PIDs out, growing the directory page when they no longer fit
+  pub fn try_sync(&mut self) -> Result<()> {
+    if !self.5 {
+      return Ok(())
+    }
+
+    let mut data = Vec::with_capacity(BLOB_DIRECTORY_LEN + self.chunks().len() * 8);
+    data.extend_from_slice(&self.len().to_be_bytes());
+    data.extend_from_slice(&(self.chunks().len() as u64).to_be_bytes());
+
+    for pid in self.chunks() {
+      data.extend_from_slice(&(*pid as u64).to_be_bytes());
+    }
+
+    let len = u32::try_from(data.len()).map_err(|_| anyhow!("Blob {} has too many chunks", self.pid()))?;
+    let page = self.pages().try_fetch(self.pid())?;
+    let mut page = self.pages().try_resize(page, len)?;
+
+    page.try_write()?.write(0, data.len(), &mut Cursor::new(&data))?;
+    self.5 = false;
+
+    Ok(())
+  }
+
+  pub fn try_free(mut self) -> Result<()> {
+    for pid in self.chunks() {
+      self.pages().try_free(self.pages().try_fetch(*pid)?)?;
+    }
+
+    self.5 = false;
+    self.pages().try_free(self.pages().try_fetch(self.pid())?)
+  }
+
+  pub fn try_create(pages: &'a PageManager) -> Result<Self> {
+    let pid = pages.try_alloc(BLOB_DIRECTORY_LEN as u32)?.pid();
+    let mut blob = Self(pages, pid, 0, 0, vec![], true);
+
+    blob.try_sync()?;
+    Ok(blob)
+  }
+
+  pub fn try_open(pages: &'a PageManager, pid: usize) -> Result<Self> {
+    let mut page = pages.try_fetch(pid)?;
+    let page = page.try_write()?;
+
+    let mut directory = vec![];
+    page.read(0, BLOB_DIRECTORY_LEN, &mut directory)?;
+    let len = u64::from_be_bytes(directory[0..8].try_into()?);
+    let count = usize::try_from(u64::from_be_bytes(directory[8..16].try_into()?))?;
+
+    // The count comes off the page so it can't be trusted to fit
+    let pids_len = count.checked_mul(8)
+      .filter(|len| len.checked_add(BLOB_DIRECTORY_LEN).is_some_and(|len| len <= page.len() - HEADER_LEN))
+      .ok_or_else(|| anyhow!("Page {} is not a blob directory", pid))?;
+
+    let mut pids = vec![];
+    page.read(BLOB_DIRECTORY_LEN, pids_len, &mut pids)?;
+    let chunks = pids.chunks(8)
+      .map(|pid| Ok(u64::from_be_bytes(pid.try_into()?) as usize))
+      .collect::<Result<Vec<usize>>>()?;
+
+    Ok(Self(pages, pid, len, 0, chunks, false))
+  }
+
+  // Private Helpers
+
+  fn try_chunk(&mut self, idx: usize) -> Result<PageGuard<'a>> {
+    let pages = self.pages();
+
+    if idx >= BLOB_MAX_CHUNKS {
+      return Err(anyhow!("Blob {} can't hold more than {} chunks", self.pid(), BLOB_MAX_CHUNKS))
+    }
+
+    while self.chunks().len() <= idx {
+      let mut chunk = pages.try_alloc(BLOB_CHUNK_LEN as u32)?;
+      chunk.try_write()?.write(0, BLOB_CHUNK_LEN, &mut io::repeat(0))?;
+      pages.advise(&mut chunk, AccessHint::Sequential);
+
+      self.4.push(chunk.pid());
+      self.5 = true;
+    }
+
+    pages.try_fetch_with(self.chunks()[idx], AccessHint::Sequential)
+  }
+}
+
+impl<'a> Read for PageBlob<'a> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.position() >= self.len() || buf.is_empty() {
+      return Ok(0)
+    }
+
+    let (idx, offset) = chunk_of(self.position());
+    let len = buf.len().min(BLOB_CHUNK_LEN - offset).min((self.len() - self.position()) as usize);
+
+    let mut chunk = self.try_chunk(idx).map_err(to_io)?;
+    let read = chunk.try_write().and_then(|page| page.read(offset, len, &mut &mut buf[..len])).map_err(to_io)?;
+
+    self.3 += read as u64;
+    Ok(read)
+  }
+}
+
+impl<'a> Write for PageBlob<'a> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    if buf.is_empty() {
+      return Ok(0)
+    }
+
+    let (idx, offset) = chunk_of(self.position());
+    let len = buf.len().min(BLOB_CHUNK_LEN - offset);
+
+    let mut chunk = self.try_chunk(idx).map_err(to_io)?;
+    let written = chunk.try_write().and_then(|mut page| page.write(offset, len, &mut &buf[..len])).map_err(to_io)?;
+
+    self.3 += written as u64;
+    if self.position() > self.len() {
+      self.2 = self.position();
+      self.5 = true;
+    }
+
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.try_sync().map_err(to_io)
+  }
+}
+
+impl<'a> Seek for PageBlob<'a> {
+  // Seeking past the end is allowed, the gap reads as zeroes once something is written
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    let position = match pos {
+      SeekFrom::Start(position) => Some(position),
+      SeekFrom::End(delta) => self.len().checked_add_signed(delta),
+      SeekFrom::Current(delta) => self.position().checked_add_signed(delta)
+    };
+
+    match position {
+      Some(position) => {
+        self.3 = position;
+        Ok(position)
+      }
+
+      None => Err(io::Error::new(ErrorKind::InvalidInput, "Seek to a negative or overflowing position"))
+    }
+  }
+}
+
+impl<'a> Drop for PageBlob<'a> {
+  fn drop(&mut self) {
+    let _ = self.try_sync();
+  }
+}
+
+fn chunk_of(position: u64) -> (usize, usize) {
+  ((position / BLOB_CHUNK_LEN as u64) as usize, (position % BLOB_CHUNK_LEN as u64) as usize)
+}
+
+fn to_io(err: anyhow::Error) -> io::Error {
+  io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_write_seek_and_reopen() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let data: Vec<u8> = (0..(BLOB_CHUNK_LEN * 2 + 100)).map(|idx| (idx % 251) as u8).collect();
+
+    let pid = {
+      let mut blob = PageBlob::try_create(&pages)?;
+      blob.write_all(&data)?;
+      blob.flush()?;
+
+      assert_eq!(3, blob.chunks().len());
+      blob.pid()
+    };
+
+    let mut blob = PageBlob::try_open(&pages, pid)?;
+    assert_eq!(data.len() as u64, blob.len());
+
+    let mut read = vec![];
+    blob.read_to_end(&mut read)?;
+    assert_eq!(data, read);
+
+    let mut tail = vec![0u8; 200];
+    blob.seek(SeekFrom::End(-200))?;
+    blob.read_exact(&mut tail)?;
+    assert_eq!(&data[data.len() - 200..], &tail[..]);
+    assert!(blob.seek(SeekFrom::Current(-(data.len() as i64) - 1)).is_err());
+
+    blob.try_free()?;
+    assert_eq!(0, pages.used_bytes());
+    Ok(())
+  }
+
+  #[test]
+  fn test_corrupt_directories_and_writes_past_the_cap_fail() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+
+    let mut page = pages.try_alloc(BLOB_DIRECTORY_LEN as u32)?;
+    let directory = [0u64.to_be_bytes(), u64::MAX.to_be_bytes()].concat();
+    page.try_write()?.write(0, directory.len(), &mut Cursor::new(&directory))?;
+    assert!(PageBlob::try_open(&pages, page.pid()).is_err());
+
+    let mut blob = PageBlob::try_create(&pages)?;
+    let used = pages.used_bytes();
+
+    blob.seek(SeekFrom::Start((BLOB_MAX_CHUNKS * BLOB_CHUNK_LEN) as u64))?;
+    assert!(blob.write(&[1]).is_err());
+    assert_eq!(used, pages.used_bytes());
+    assert!(blob.chunks().is_empty());
+    Ok(())
+  }
+}
@@ -1,29 +1,85 @@
+mod access_hint;
 mod read_guard;
 mod share_guard;
 mod write_guard;
 
 use anyhow::{ Result };
-use crate::{ Page };
+use std::sync::Arc;
+use crate::{ Page, PageChain, PageFrame, PageSWIP, PageVLDS, HEADER_LEN };
 
+pub use access_hint::*;
 pub use read_guard::*;
 pub use share_guard::*;
 pub use write_guard::*;
 
+// A page handed out by the page manager, the frame it holds isn't reused until the guard is dropped
 #[derive(Debug)]
-pub struct PageGuard<'a>(Page<'a>);
+pub struct PageGuard<'a>(Page<'a>, AccessHint, Option<Arc<PageFrame>>);
 
 impl<'a> PageGuard<'a> {
   pub fn new(page: Page<'a>) -> Self {
-    Self(page)
+    Self(page, AccessHint::default(), None)
+  }
+
+  pub fn pid(&self) -> usize {
+    PageSWIP::pid(self.0.swip().value())
+  }
+
+  pub fn cid(&self) -> usize {
+    PageSWIP::cid(self.0.swip().value())
+  }
+
+  // Bytes past the header
+  pub fn data_len(&self) -> usize {
+    self.0.len() - HEADER_LEN
+  }
+
+  pub fn chain(&self) -> PageChain<'_> {
+    self.0.chain()
+  }
+
+  pub fn vlds(&self) -> PageVLDS<'_> {
+    self.0.vlds()
+  }
+
+  pub fn hint(&self) -> AccessHint {
+    self.1
+  }
+
+  pub fn with_hint(mut self, hint: AccessHint) -> Self {
+    self.1 = hint;
+    self
+  }
+
+  pub fn set_hint(&mut self, hint: AccessHint) {
+    self.1 = hint;
+  }
+
+  pub fn with_frame(mut self, frame: Arc<PageFrame>) -> Self {
+    self.2 = Some(frame);
+    self
+  }
+
+  pub fn take_frame(&mut self) -> Option<Arc<PageFrame>> {
+    self.2.take()
   }
 
   //
-  // try_read
   // try_share
   // try_write
   //
 
-  pub fn try_write(&'a mut self) -> Result<WriteGuard<'a>> {
+  // Optimistic reads go through PageManager::optimistic
+  pub fn page(&self) -> &Page<'a> {
+    &self.0
+  }
+
+  // A single attempt at the exclusive latch for callers that would rather restart than wait
+  pub fn try_latch(&mut self) -> Option<WriteGuard<'_, 'a>> {
+    WriteGuard::try_latch(self.page_mut())
+  }
+
+  pub fn try_write(&mut self) -> Result<WriteGuard<'_, 'a>> {
     WriteGuard::try_new(self.page_mut())
   }
 
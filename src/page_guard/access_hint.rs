@@ -0,0 +1,17 @@
+//
+// How a page is about to be used, the cooling queue places pages by it
+//
+// Normal     - No expectations, random cooling with a second chance
+// Sequential - Part of a scan, cools right away and a touch doesn't save it
+// WillNeed   - Hot, never picked for cooling while better victims exist
+// DontNeed   - Done with it, the next page to be evicted
+//
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccessHint {
+  #[default]
+  Normal,
+  Sequential,
+  WillNeed,
+  DontNeed
+}
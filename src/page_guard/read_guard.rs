@@ -5,61 +5,73 @@ use std::{
   ops::Deref
 };
 
-use crate::{PageVLDS, Page };
+use crate::{ EpochGuard, PageSWIP, PageVLDS, Page, EPOCH_IDLE };
 
+// Page, its version and SWIP when the guard was taken
 #[derive(Debug)]
-pub struct ReadGuard<'a>(&'a Page<'a>, usize);
+pub struct ReadGuard<'g, 'a>(&'g Page<'a>, usize, usize);
 
-impl<'a> Deref for ReadGuard<'a> {
+impl<'g, 'a> Deref for ReadGuard<'g, 'a> {
   type Target = Page<'a>;
   fn deref(&self) -> &Self::Target {
-    self.0.deref()
+    self.0
   }
 }
 
 // Methods
 
-impl<'a> ReadGuard<'a> {
-  fn version(&self) -> usize {
+impl<'g, 'a> ReadGuard<'g, 'a> {
+  pub fn version(&self) -> usize {
     self.1
   }
 
-  // pub fn lock_optimistic(&mut self) -> Option<OptimisticPageGuard<'a>> {
-  //   // If the latch is open then return page guard
-  //   // If the latch is shared then return page guard
-  //   // If the latch is exclusive then wait for it to be unlocked, loop
   //
-  //   None
-  // }
+  // False once a writer latched or changed the page since the guard was
+  //  taken or the frame went to another page. Versions carry on from one
+  //  page in a frame to the next so a reused frame doesn't validate either
+  //
+  pub fn is_valid(&self) -> bool {
+    let value = self.vlds().value();
+    PageVLDS::version(value) == self.version() && !PageVLDS::is_exclusive(PageVLDS::latch(value)) && self.swip().value() == self.2
+  }
 }
 
 // Associated
 
-impl<'a> ReadGuard<'a> {
+impl<'g, 'a> ReadGuard<'g, 'a> {
+  //
+  // None while a writer holds the page, which may never end for a frame that
+  //  was freed, evicted or resized under the reader so it restarts instead of
+  //  waiting. The epoch keeps the frame from being handed out again while the
+  //  read is in flight, PageManager::optimistic checks that it is the epoch
+  //  of the manager the frame belongs to
+  //
+  pub(crate) fn try_new(page: &'g Page<'a>, epoch: &'g EpochGuard) -> Option<Self> {
+    let value = page.vlds().value();
+    let swip = page.swip().value();
+
+    if epoch.epoch() == EPOCH_IDLE || PageVLDS::is_exclusive(PageVLDS::latch(value)) || PageSWIP::is_cleared(swip) {
+      None
+    } else {
+      Some(Self(page, PageVLDS::version(value), swip))
+    }
+  }
+
   // Returns None if a read couldn't be performed due to a version mismatch
   //  Otherwise returns Some(usize) which is the number of bytes written/read
-  pub fn try_read<D: AsRef<[u8]> + Write>(&'a mut self, offset: usize, len: usize, dest: &mut D) -> Result<Option<usize>> {
-    let vlds = self.vlds();
-    let data = self.data();
-
-    if PageVLDS::version(vlds.value()) != self.version() {
+  pub fn try_read<D: AsRef<[u8]> + Write>(&self, offset: usize, len: usize, dest: &mut D) -> Result<Option<usize>> {
+    if !self.is_valid() {
       return Ok(None)
     }
 
-    loop {
-      while PageVLDS::is_exclusive(PageVLDS::latch(vlds.value())) {
-        core::hint::spin_loop();
-      }
+    // Read into dest buffer
+    let bytes_read = self.data().try_read(offset, len, dest)?;
 
-      // Read into dest buffer
-      let bytes_read = data.try_read(offset, len, dest)?;
-
-      // Recheck version
-      return if PageVLDS::version(vlds.value()) != self.version() {
-        Ok(None)
-      } else {
-        Ok(Some(bytes_read))
-      }
+    // Recheck version, a writer that latched during the read may not have bumped it yet
+    if self.is_valid() {
+      Ok(Some(bytes_read))
+    } else {
+      Ok(None)
     }
   }
-}
\ No newline at end of file
+}
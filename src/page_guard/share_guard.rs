@@ -2,12 +2,16 @@ use anyhow::{ Result };
 use crate::{ Page };
 
 #[derive(Debug)]
-pub struct ShareGuard<'a>(&'a Page<'a>);
+pub struct ShareGuard<'g, 'a>(&'g Page<'a>);
 
 // Associated
 
-impl<'a> ShareGuard<'a> {
-  pub fn try_new(_: &'a Page<'a>) -> Result<Self> {
+impl<'g, 'a> ShareGuard<'g, 'a> {
+  pub fn page(&self) -> &'g Page<'a> {
+    self.0
+  }
+
+  pub fn try_new(_: &'g Page<'a>) -> Result<Self> {
     todo!()
 
     // pub fn lock_shared(&mut self) -> Option<SharedPageGuard<'a>> {
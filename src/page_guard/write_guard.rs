@@ -1,4 +1,7 @@
-use anyhow::Result;
+use anyhow::{
+  anyhow, Result
+};
+
 use core::hint::spin_loop;
 
 use std::{
@@ -6,26 +9,28 @@ use std::{
   ops::{ Deref, DerefMut }
 };
 
-use crate::{PageVLDS, Page };
+use crate::{ Page, PageSWIP, PageVLDS };
 
 #[derive(Debug)]
-pub struct WriteGuard<'a>(&'a mut Page<'a>);
+pub struct WriteGuard<'g, 'a>(&'g mut Page<'a>);
 
-// impl<'a> Drop for WriteGuard<'a> {
-//   fn drop(&mut self) {
-//     // bump version
-//     // release latch
-//   }
-// }
+impl<'g, 'a> Drop for WriteGuard<'g, 'a> {
+  fn drop(&mut self) {
+    // Only the dirty bit can change under us while we hold the latch
+    while self.vlds().release_write().is_err() {
+      spin_loop();
+    }
+  }
+}
 
-impl<'a> Deref for WriteGuard<'a> {
+impl<'g, 'a> Deref for WriteGuard<'g, 'a> {
   type Target = Page<'a>;
   fn deref(&self) -> &Self::Target {
     self.0.deref()
   }
 }
 
-impl<'a> DerefMut for WriteGuard<'a> {
+impl<'g, 'a> DerefMut for WriteGuard<'g, 'a> {
   fn deref_mut(&mut self) -> &mut Self::Target {
     self.0.deref_mut()
   }
@@ -33,23 +38,47 @@ impl<'a> DerefMut for WriteGuard<'a> {
 
 // Methods
 
-impl<'a> WriteGuard<'a> {
+impl<'g, 'a> WriteGuard<'g, 'a> {
   // todo: Add ability to downgrade this to a shared lock via Into<ShareGuard<'a>>
 
-  pub fn read<W: Write>(&'a self, offset: usize, len: usize, dest: &mut W) -> Result<usize> {
-    Ok(self.data().try_read(offset, len, dest)?)
+  pub fn read<W: Write>(&self, offset: usize, len: usize, dest: &mut W) -> Result<usize> {
+    self.data().try_read(offset, len, dest)
   }
 
-  pub fn write<R: Read>(&'a mut self, offset: usize, len: usize, data: &mut R) -> Result<usize> {
-    Ok(self.data_mut().try_write(offset, len, data)?)
+  // Hands the latch over to a copy of the page in a frame the caller latched, returns the old frame still latched
+  pub fn move_to(&mut self, page: Page<'a>) -> Page<'a> {
+    std::mem::replace(self.0, page)
+  }
+
+  // Marks the page dirty so eviction writes it back
+  pub fn write<R: Read>(&mut self, offset: usize, len: usize, data: &mut R) -> Result<usize> {
+    let written = self.data_mut().try_write(offset, len, data)?;
+
+    // We hold the latch so nobody else changes the VLDS
+    while PageVLDS::dirty(self.vlds().value()) == 0 && self.vlds().mark_dirty().is_err() {
+      spin_loop();
+    }
+
+    Ok(written)
   }
 }
 
 // Associated
 
-impl<'a> WriteGuard<'a> {
-  // A very unfair test and spin lock
-  pub fn try_new(page: &'a mut Page<'a>) -> Result<Self> {
+impl<'g, 'a> WriteGuard<'g, 'a> {
+  // A single attempt for callers that would rather move on than wait, like eviction
+  pub fn try_latch(page: &'g mut Page<'a>) -> Option<Self> {
+    let vlds = page.vlds();
+
+    if PageVLDS::is_open(PageVLDS::latch(vlds.value())) && vlds.latch_write().is_ok() {
+      Some(Self(page))
+    } else {
+      None
+    }
+  }
+
+  // A very unfair test and spin lock, fails for a frame the page was freed or moved out of
+  pub fn try_new(page: &'g mut Page<'a>) -> Result<Self> {
     let vlds = page.vlds();
     let mut value = vlds.value();
     let mut latch = PageVLDS::latch(value);
@@ -64,6 +93,10 @@ impl<'a> WriteGuard<'a> {
 
       // Works well for fast latches, probably bad for slow latches
       while !PageVLDS::is_open(PageVLDS::latch(vlds.value())) {
+        if PageSWIP::is_cleared(page.swip().value()) {
+          return Err(anyhow!("Page was freed or moved while it was held, fetch it again"))
+        }
+
         spin_loop();
       }
 
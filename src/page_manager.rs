@@ -1,36 +1,197 @@
 mod address_pool;
+mod compressed_tier;
+mod dirty_pages;
+mod epoch_worker;
+mod epochs;
+mod eviction_policy;
+mod page_frame;
 mod page_id_pool;
+mod page_pins;
+mod page_tiers;
+mod pinned_page;
+mod prefetcher;
+mod read_ahead;
+mod version_gc;
 
 use anyhow::{
   anyhow, Result
 };
 
-use std::sync::atomic::{
-  AtomicUsize, Ordering
+use parking_lot::{ Mutex, RwLock };
+
+use std::{
+  collections::HashMap,
+  io::Cursor,
+  sync::{
+    Arc,
+    atomic::{ AtomicUsize, Ordering }
+  },
+  thread::{ self, JoinHandle },
+  time::Duration
 };
 
 use crate::{
-  MAX_CLASS_ID, MIN_CLASS_ID,
-  page_class, Page, PageGuard, PageSWIP
+  HEADER_LEN, MAX_CLASS_ID, MIN_CLASS_ID, SWIP_LEN, VLDS_LEN,
+  page_class, AccessHint, Page, PageChain, PageGuard, PageLocation, PageSWIP, PageStore, PageVLDS, ReadGuard, StorageTier, Swip, WriteGuard, STORAGE_TIERS
 };
 
 pub use address_pool::*;
+pub use compressed_tier::*;
+pub use dirty_pages::*;
+pub use epoch_worker::*;
+pub use epochs::*;
+pub use eviction_policy::*;
+pub use page_frame::*;
 pub use page_id_pool::*;
+pub use page_pins::*;
+pub use page_tiers::*;
+pub use pinned_page::*;
+pub use prefetcher::*;
+pub use read_ahead::*;
+pub use version_gc::*;
 
 // Consider making PageClass wrap this
 type ClassPools = Vec<AddressPool>;
 
+// The frame of every page that is in memory, shared with the guards handed out for it
+type ResidentPages = RwLock<HashMap<usize, Arc<PageFrame>>>;
+
 #[derive(Debug)]
-pub struct PageManager(ClassPools, PageIdPool, AtomicUsize);
+pub struct PageManager {
+  pools: ClassPools,
+  page_ids: PageIdPool,
+  used: AtomicUsize,
+  page_tiers: PageTiers,
+  compressed_tier: Option<CompressedTier>,
+  prefetcher: Prefetcher,
+  budget: usize,
+  resident_pages: ResidentPages,
+  eviction_policy: Mutex<Box<dyn EvictionPolicy>>,
+  page_pins: PagePins,
+  versions: VersionGc,
+  epochs: Epochs,
+  dirty_pages: DirtyPages
+}
 
 impl PageManager {
   pub fn used_bytes(&self) -> usize {
-    self.2.load(Ordering::Acquire)
+    self.used.load(Ordering::Acquire)
+  }
+
+  // Bytes of frames in use before pages start getting evicted
+  pub fn budget(&self) -> usize {
+    self.budget
+  }
+
+  pub fn pinned_bytes(&self) -> usize {
+    self.page_pins().pinned_bytes()
+  }
+
+  pub fn versions(&self) -> &VersionGc {
+    &self.versions
+  }
+
+  // Workers reading pages optimistically register here
+  pub fn epochs(&self) -> &Epochs {
+    &self.epochs
+  }
+
+  // Pages with logged writes that haven't been written to their store since
+  pub fn dirty_pages(&self) -> &DirtyPages {
+    &self.dirty_pages
+  }
+
+  // Hands the frames no optimistic reader can still be in back to their pools
+  pub fn try_reclaim(&self) -> Result<usize> {
+    let frames = self.epochs().reclaim();
+
+    for (addr, cid) in frames.iter() {
+      self.try_class_pool(page_class::index_of(*cid))?.reclaim(*addr);
+      self.decrement_used(page_class::size_of(*cid));
+    }
+
+    Ok(frames.len())
+  }
+
+  pub fn store(&self, tier: StorageTier) -> Option<&PageStore> {
+    self.page_tiers().store(tier)
+  }
+
+  pub fn is_resident(&self, pid: usize) -> bool {
+    self.resident_pages().read().contains_key(&pid)
+  }
+
+  //
+  // The page a swizzled reference points at while its frame still holds it,
+  //  None once it was evicted, freed or moved to another class and has to be
+  //  fetched by PID again. Eviction, try_free and try_resize clear the SWIP
+  //  of the frame they take the page out of, which is what every reference
+  //  into that frame checks here. The epoch keeps the frame from being
+  //  handed to another page while it is read
+  //
+  pub fn swizzled<'e>(&self, swip: Swip, pid: usize, epoch: &'e EpochGuard) -> Option<Page<'e>> {
+    if !epoch.is_of(self.epochs()) {
+      return None
+    }
+
+    let addr = swip.addr()?;
+    let pool = self.pools.iter().find(|pool| pool.contains(addr))?;
+
+    let page = Page::resident(addr, pool.cid());
+    let value = page.swip().value();
+
+    (PageSWIP::tag(value) == 1 && PageSWIP::pid(value) == pid && PageSWIP::cid(value) == pool.cid()).then_some(page)
+  }
+
+  //
+  // An optimistic read of one of this manager's pages that only holds as
+  //  long as the page's version doesn't change. None while it's being
+  //  written, once its frame was taken from it or when the epoch belongs to
+  //  another manager and so wouldn't keep the frame from being reused
+  //
+  pub fn optimistic<'g, 'a>(&self, page: &'g Page<'a>, epoch: &'g EpochGuard) -> Option<ReadGuard<'g, 'a>> {
+    let value = page.swip().value();
+    let is_ours = self.pools.iter().any(|pool| pool.cid() == PageSWIP::cid(value) && pool.contains(page.addr()));
+
+    if !epoch.is_of(self.epochs()) || !is_ours || PageSWIP::is_cleared(value) {
+      return None
+    }
+
+    ReadGuard::try_new(page, epoch)
   }
 
   pub fn try_free(&self, mut page: PageGuard) -> Result<()> {
-    let page = page.try_write()?;
+    let held = page.take_frame();
+    let latched = page.try_write()?;
+
+    // Nothing here reads the frame once it is retired so it can go back to its pool right away
+    drop(held);
+    self.try_free_latched(latched)
+  }
+
+  //
+  // try_free for a page the caller already latched, the latch stays with the
+  //  frame. Holding the page's fault-in slot until its PID is free keeps a
+  //  lookup that still found the PID from reading the page back into a
+  //  frame while it is taken out of the stores
+  //
+  pub fn try_free_latched(&self, page: WriteGuard) -> Result<()> {
+    let addr = page.addr();
     let swip = page.swip().value();
+    let pid = PageSWIP::pid(swip);
+
+    if self.page_pins().is_pinned(pid) {
+      return Err(anyhow!("Page {} is pinned and can't be freed", pid))
+    }
+
+    self.prefetcher().begin(pid);
+
+    // Unreachable before it's retired so readers entering a later epoch never find it
+    let frame = self.untrack_frame(pid, addr);
+
+    // The frame goes back to its pool still latched, the next alloc resets it. Other holders find it cleared
+    page.swip().clear();
+    std::mem::forget(page);
 
     //
     // This could be a fizzled page in which case
@@ -38,84 +199,1277 @@ impl PageManager {
     //  free it again so it seems like we just silently return?
     //
 
-    // todo: very weird static design choices here
-    let cid = PageSWIP::cid(swip);
-    let idx = page_class::index_of(cid);
+    let frame = frame.unwrap_or_else(|| Arc::new(PageFrame::new(addr, PageSWIP::cid(swip))));
+    let freed = self.try_retire_frame(frame).and_then(|retired| match retired {
+      true => {
+        self.dirty_pages().clean(pid);
+        self.page_tiers().remove(pid);
+        self.page_id_pool().free(pid)
+      }
 
-    match self.try_class_pool(idx) {
-      Ok(pool) => {
-        if pool.free(addr) {
-          self.decrement_used(page_class::size_of(cid));
-        }
+      false => Ok(())
+    });
 
-        // Should this result in an error?
+    self.prefetcher().complete(pid);
+
+    // Should an invalid free result in an error?
+    freed
+  }
+
+  //
+  // Frees the page without faulting it in when it isn't resident. Holding the
+  //  page's fault-in slot keeps anyone from reading it back into a frame
+  //  while it is taken out of the stores
+  //
+  pub fn try_free_stored(&self, pid: usize) -> Result<()> {
+    if !self.is_resident(pid) {
+      self.prefetcher().begin(pid);
+      let freed = match self.is_resident(pid) {
+        true => None,
+        false => Some(self.try_free_unresident(pid))
+      };
+
+      self.prefetcher().complete(pid);
+      if let Some(freed) = freed {
+        return freed
       }
+    }
+
+    self.try_free(self.try_fetch(pid)?)
+  }
+
+  //
+  // Gives the page's frame back while keeping its PID for a later try_fetch
+  //  Pages that compress into a smaller class go to the compressed tier,
+  //  everything else is written to the store of its storage tier
+  //
+  pub fn try_evict(&self, mut page: PageGuard) -> Result<()> {
+    let pid = page.pid();
+    let frame = match page.take_frame().or_else(|| self.resident_frame(pid)) {
+      Some(frame) => frame,
+      None => return Err(anyhow!("Page {} isn't resident", pid))
+    };
+
+    if self.try_evict_latched(page.try_write()?, frame)? {
+      Ok(())
+    } else {
+      Err(anyhow!("Page {} is pinned or held elsewhere and can't be evicted", pid))
+    }
+  }
 
-      Err(err) => Err(err)
+  // Writes every dirty page of the compressed tier to its store and empties the tier
+  pub fn try_spill(&self) -> Result<()> {
+    if let Some(tier) = self.compressed_tier() {
+      for (pid, frame) in tier.drain() {
+        self.try_spill_frame(tier, pid, frame)?;
+      }
     }
 
     Ok(())
   }
 
+  pub fn try_alloc(&self, len: u32) -> Result<PageGuard<'_>> {
+    self.try_alloc_in(len, StorageTier::default())
+  }
+
   // todo: make this more thread-safe
-  pub fn try_alloc(&self, len: u32) -> Result<PageGuard> {
+  pub fn try_alloc_in(&self, len: u32, tier: StorageTier) -> Result<PageGuard<'_>> {
     let cid = page_class::to_fit(len)?;
-    let idx = page_class::index_of(cid);
+    let address = self.try_alloc_frame(cid)?;
+    let pid = self.page_id_pool().next()?;
 
-    match self.try_class_pool(idx) {
-      Ok(class) => {
-        if let Some(address) = class.alloc() {
-          let pid = self.page_id_pool().next();
-          self.increment_used(page_class::size_of(cid));
-          return match Page::try_alloc(address, pid, cid) {
-            Err(err) => {
-              // todo: What should we do if we can't activate a frame?
-              class.free(address);
-              Err(err)
-            }
-
-            Ok(page) => Ok(PageGuard::new(page))
-          }
-        }
+    if !self.page_tiers().is_empty() {
+      self.page_tiers().place(pid, tier);
+    }
+
+    match Page::try_alloc(address, pid, cid) {
+      Err(err) => {
+        // todo: What should we do if we can't activate a frame?
+        self.try_release_frame(address, cid)?;
+        Err(err)
+      }
+
+      Ok(page) => {
+        let frame = self.track(pid, address, cid, AccessHint::default());
+        Ok(PageGuard::new(page).with_frame(frame))
+      }
+    }
+  }
+
+  //
+  // Moves the page into the class that fits the new length under the same PID
+  //  Growing zeroes the new bytes and shrinking drops whatever doesn't fit,
+  //  the next flush relocates the page to a slot of the new class. Other
+  //  guards of the page keep the old frame from being reused but find it
+  //  cleared and have to fetch the page again, swizzled references to it
+  //  stop resolving (see swizzled) and get the new frame by PID
+  //
+  pub fn try_resize<'a>(&'a self, mut page: PageGuard<'a>, len: u32) -> Result<PageGuard<'a>> {
+    let held = page.take_frame();
+    let mut latched = page.try_write()?;
+
+    if PageSWIP::cid(latched.swip().value()) == page_class::to_fit(len)? {
+      drop(latched);
+      return Ok(match held {
+        Some(frame) => page.with_frame(frame),
+        None => page
+      })
+    }
+
+    // Nothing here reads the old frame once it is retired so it can go back to its pool right away
+    drop(held);
+    let frame = self.try_resize_latched(&mut latched, len)?;
+    drop(latched);
+
+    Ok(page.with_frame(frame))
+  }
+
+  //
+  // try_resize for a page the caller already latched. The new frame is
+  //  latched before anyone can find it and the guard moves over to it, so
+  //  nobody reads the copied bytes or writes the page before the caller is
+  //  done with it. The caller's page guard holds the old frame until it's
+  //  given the returned one
+  //
+  pub fn try_resize_latched<'a>(&'a self, page: &mut WriteGuard<'_, 'a>, len: u32) -> Result<Arc<PageFrame>> {
+    let swip = page.swip().value();
+    let (pid, cid) = (PageSWIP::pid(swip), page_class::to_fit(len)?);
+
+    if self.page_pins().is_pinned(pid) {
+      return Err(anyhow!("Page {} is pinned and can't be resized", pid))
+    }
+
+    let address = self.try_alloc_frame(cid)?;
+    let resized = Page::try_fetch(address, pid, cid, |frame| {
+      let copied = frame.len().min(page.len());
+      frame[..copied].copy_from_slice(&page.bytes()[..copied]);
+      frame[copied..].fill(0);
+      Ok(())
+    });
+
+    let resized = match resized {
+      Ok(resized) => resized,
+      Err(err) => {
+        self.try_release_frame(address, cid)?;
+        return Err(err)
+      }
+    };
+
+    // Nobody else knows the frame yet
+    let _ = resized.vlds().mark_dirty();
+    let _ = resized.vlds().latch_write();
+
+    let frame = Arc::new(PageFrame::new(address, cid));
+    let previous = self.resident_pages().write().insert(pid, frame.clone());
+
+    // The old frame goes back to its pool still latched, other holders find it cleared
+    let old = page.move_to(resized);
+    old.swip().clear();
+    self.try_retire_frame(previous.unwrap_or_else(|| Arc::new(PageFrame::new(old.addr(), PageSWIP::cid(swip)))))?;
+
+    Ok(frame)
+  }
+
+  //
+  // Makes the page writable by the transaction without losing what older
+  //  snapshots see: its current contents are copied to a new PID in the same
+  //  class which becomes the newest past version, and the page is stamped
+  //  with the transaction id until it commits
+  //
+  pub fn try_cow<'a>(&'a self, page: PageGuard<'a>, txn: usize) -> Result<PageGuard<'a>> {
+    self.try_branch(page, txn, false)
+  }
+
+  // Pushes another version of a page the transaction already wrote so a savepoint can return to the current one
+  pub fn try_fork<'a>(&'a self, page: PageGuard<'a>, txn: usize) -> Result<PageGuard<'a>> {
+    self.try_branch(page, txn, true)
+  }
+
+  // Frees the versions under the head the transaction wrote itself, only the head is stamped at commit
+  pub fn try_squash(&self, pid: usize, txn: usize) -> Result<()> {
+    let mut head = self.try_fetch(pid)?;
+    if head.chain().stamp() != txn {
+      return Ok(())
+    }
+
+    loop {
+      let past = match head.chain().past() {
+        0 => return Ok(()),
+        past => self.try_fetch(past)?
+      };
+
+      if past.chain().stamp() != txn {
+        return Ok(())
+      }
+
+      {
+        let latched = head.try_write()?;
+        latched.chain().set_past(past.chain().past());
+        let _ = latched.vlds().mark_dirty();
+      }
+
+      self.try_free(past)?;
+    }
+  }
+
+  // Puts the newest past version back in place of the version the transaction wrote
+  pub fn try_rollback(&self, pid: usize, txn: usize) -> Result<()> {
+    let mut head = self.try_fetch(pid)?;
+    if head.chain().stamp() != txn {
+      return Ok(())
+    }
+
+    let mut past = match head.chain().past() {
+      0 => return Err(anyhow!("Page {} has no past version to roll back to", pid)),
+      past => self.try_fetch(past)?
+    };
+
+    {
+      let mut latched = head.try_write()?;
+      let previous = past.try_write()?;
+
+      if latched.len() != previous.len() {
+        return Err(anyhow!("Page {} changed class since its past version", pid))
+      }
+
+      let data = &previous.bytes()[HEADER_LEN..];
+      latched.write(0, data.len(), &mut Cursor::new(data))?;
+      latched.chain().set_past(previous.chain().past());
+      latched.chain().set_stamp(previous.chain().stamp());
+      let _ = latched.vlds().mark_dirty();
+    }
+
+    self.try_free(past)
+  }
+
+  // Walks the version chain from the page to the newest version the snapshot sees
+  pub fn try_fetch_visible(&self, pid: usize, snapshot: usize, txn: usize) -> Result<Option<PageGuard<'_>>> {
+    let mut pid = pid;
 
-        todo!("No more free space, write some memory to disk")
+    loop {
+      let page = self.try_fetch(pid)?;
+      let chain = page.chain();
+      let (past, stamp) = (chain.past(), chain.stamp());
+
+      if PageChain::is_visible(stamp, snapshot, txn) {
+        return Ok(Some(page))
+      }
+
+      // The page was created after the snapshot was taken
+      if past == 0 {
+        return Ok(None)
+      }
+
+      pid = past;
+    }
+  }
+
+  // Collects the version chains of up to `batch` heads, returns how many versions were freed
+  pub fn try_collect_versions(&self, batch: usize) -> Result<usize> {
+    let horizon = self.versions().horizon();
+    let mut freed = 0;
+
+    for head in self.versions().next_batch(batch) {
+      let (collected, more) = self.try_collect_chain(head, horizon)?;
+      freed += collected;
+
+      if more {
+        self.versions().track(head);
+      }
+    }
+
+    Ok(freed)
+  }
+
+  // Collects a batch every interval until the page manager is dropped
+  pub fn spawn_version_gc(self: &Arc<Self>, interval: Duration, batch: usize) -> JoinHandle<Result<()>> {
+    let pages = Arc::downgrade(self);
+
+    thread::spawn(move || {
+      loop {
+        thread::sleep(interval);
+
+        match pages.upgrade() {
+          Some(pages) => pages.try_collect_versions(batch)?,
+          None => return Ok(())
+        };
       }
+    })
+  }
+
+  // Writes the page to the store of the tier it was placed in and marks it clean
+  pub fn try_flush(&self, page: &mut PageGuard) -> Result<PageLocation> {
+    let page = page.try_write()?;
+    self.try_flush_latched(&page)
+  }
 
-      Err(err) => Err(err)
+  //
+  // Writes up to `batch` pages with the oldest recovery LSNs to their stores,
+  //  which is what lets a checkpoint truncate the log further. Returns how
+  //  many pages were written
+  //
+  pub fn try_flush_oldest(&self, batch: usize) -> Result<usize> {
+    let mut flushed = 0;
+
+    for (pid, _) in self.dirty_pages().entries().into_iter().take(batch) {
+      if let Some(frame) = self.compressed_tier().and_then(|tier| Some((tier, tier.remove(pid)?))) {
+        self.try_spill_frame(frame.0, pid, frame.1)?;
+        flushed += 1;
+      } else if self.is_resident(pid) {
+        self.try_flush(&mut self.try_fetch(pid)?)?;
+        flushed += 1;
+      }
     }
+
+    Ok(flushed)
+  }
+
+  // Starts faulting in every unswizzled swip without blocking, returns how many were queued
+  pub fn prefetch(self: &Arc<Self>, swips: &[Swip]) -> usize {
+    self.prefetcher().prefetch(self, swips)
+  }
+
+  pub fn try_fetch(&self, pid: usize) -> Result<PageGuard<'_>> {
+    self.try_fetch_with(pid, AccessHint::default())
   }
 
-  // pub fn try_fetch<'a>(&self, _handle: &FizzledPageSWIP) -> Result<PageGuard> {
-  //   todo!("Check the fridge and freezer for this page")
-  // }
+  // Resident pages are handed out as they are, everything else is faulted in once
+  pub fn try_fetch_with(&self, pid: usize, hint: AccessHint) -> Result<PageGuard<'_>> {
+    let frame = match self.resident_frame(pid) {
+      Some(frame) => frame,
+      None => {
+        self.prefetcher().begin(pid);
+        let frame = match self.resident_frame(pid) {
+          Some(frame) => Ok(frame),
+          None => self.try_fault_in(pid, hint)
+        };
+
+        self.prefetcher().complete(pid);
+        frame?
+      }
+    };
+
+    // A missed access only makes the page look colder, lookups don't wait on eviction for it
+    if let Some(mut policy) = self.eviction_policy().try_lock() {
+      policy.access(pid, hint);
+    }
+
+    // The guard holds the frame so it stays resident even if it is picked for eviction right away
+    Ok(PageGuard::new(Page::resident(frame.addr(), frame.cid())).with_hint(hint).with_frame(frame))
+  }
+
+  // Fetches the page and keeps it resident until the pin is dropped
+  pub fn try_pin(&self, pid: usize) -> Result<PinnedPage<'_>> {
+    loop {
+      self.try_fetch(pid)?;
+
+      if let Some((address, cid)) = self.page_pins().try_pin(pid, &|| self.resident(pid))? {
+        return Ok(PinnedPage::new(self.page_pins(), pid, address, cid))
+      }
+    }
+  }
+
+  // Changes how the eviction policy treats the page from here on
+  pub fn advise(&self, page: &mut PageGuard, hint: AccessHint) {
+    page.set_hint(hint);
+    self.eviction_policy().lock().access(page.pid(), hint);
+  }
+
+  pub fn try_checkpoint(&self) -> Result<()> {
+    self.try_spill()?;
+    self.page_id_pool().try_checkpoint()?;
+
+    for tier in STORAGE_TIERS {
+      if let Some(store) = self.page_tiers().store(tier) {
+        store.try_checkpoint(self, tier)?;
+      }
+    }
+
+    Ok(())
+  }
 
   pub fn try_new(pool_size: usize) -> Result<Self> {
+    Self::try_new_with(pool_size, PageIdPool::new(), PageTiers::new())
+  }
+
+  pub fn try_new_with(pool_size: usize, page_ids: PageIdPool, page_tiers: PageTiers) -> Result<Self> {
     let mut pools: ClassPools = vec![];
 
     for cid in MIN_CLASS_ID..=MAX_CLASS_ID {
       pools.push(AddressPool::try_new(pool_size, cid)?)
     }
 
-    Ok(Self(pools, PageIdPool::new(), AtomicUsize::new(0)))
+    Ok(Self {
+      pools,
+      page_ids,
+      used: AtomicUsize::new(0),
+      page_tiers,
+      compressed_tier: None,
+      prefetcher: Prefetcher::default(),
+      budget: pool_size,
+      resident_pages: RwLock::new(HashMap::new()),
+      eviction_policy: Mutex::new(Box::new(CoolingQueue::new())),
+      page_pins: PagePins::new(pool_size / 2),
+      versions: VersionGc::new(),
+      epochs: Epochs::new(),
+      dirty_pages: DirtyPages::new()
+    })
+  }
+
+  pub fn with_compressed_tier(mut self, tier: CompressedTier) -> Self {
+    self.compressed_tier = Some(tier);
+    self
+  }
+
+  // Bytes of pinned pages, half the budget by default
+  pub fn with_pin_limit(mut self, limit: usize) -> Self {
+    self.page_pins = PagePins::new(limit);
+    self
+  }
+
+  // Only takes effect before any page was allocated or fetched
+  pub fn with_eviction_policy(mut self, policy: Box<dyn EvictionPolicy>) -> Self {
+    self.eviction_policy = Mutex::new(policy);
+    self
   }
 
   // Private Accessors + Helpers
   fn page_id_pool(&self) -> &PageIdPool {
-    &self.1
+    &self.page_ids
+  }
+
+  fn page_tiers(&self) -> &PageTiers {
+    &self.page_tiers
+  }
+
+  fn compressed_tier(&self) -> Option<&CompressedTier> {
+    self.compressed_tier.as_ref()
+  }
+
+  fn prefetcher(&self) -> &Prefetcher {
+    &self.prefetcher
+  }
+
+  fn resident_pages(&self) -> &ResidentPages {
+    &self.resident_pages
+  }
+
+  fn eviction_policy(&self) -> &Mutex<Box<dyn EvictionPolicy>> {
+    &self.eviction_policy
+  }
+
+  fn page_pins(&self) -> &PagePins {
+    &self.page_pins
+  }
+
+  fn resident(&self, pid: usize) -> Option<(usize, usize)> {
+    self.resident_frame(pid).map(|frame| (frame.addr(), frame.cid()))
+  }
+
+  fn resident_frame(&self, pid: usize) -> Option<Arc<PageFrame>> {
+    self.resident_pages().read().get(&pid).cloned()
+  }
+
+  fn track(&self, pid: usize, addr: usize, cid: usize, hint: AccessHint) -> Arc<PageFrame> {
+    let frame = Arc::new(PageFrame::new(addr, cid));
+    self.resident_pages().write().insert(pid, frame.clone());
+    self.eviction_policy().lock().insert(pid, hint);
+    frame
+  }
+
+  // Stops tracking the page if it is still resident in the frame at the address
+  fn untrack_frame(&self, pid: usize, addr: usize) -> Option<Arc<PageFrame>> {
+    let frame = {
+      let mut resident = self.resident_pages().write();
+      match resident.get(&pid) {
+        Some(frame) if frame.addr() == addr => resident.remove(&pid),
+        _ => None
+      }
+    };
+
+    if frame.is_some() {
+      self.eviction_policy().lock().remove(pid);
+    }
+
+    frame
+  }
+
+  //
+  // Stops tracking the page unless a guard other than the evicting one still
+  //  holds the frame, guards are only handed out under the read lock so none
+  //  can appear while we look
+  //
+  fn untrack_unheld(&self, pid: usize, frame: &Arc<PageFrame>) -> bool {
+    let unheld = {
+      let mut resident = self.resident_pages().write();
+      let unheld = resident.get(&pid).is_some_and(|tracked| Arc::ptr_eq(tracked, frame)) && Arc::strong_count(frame) == 2;
+
+      if unheld {
+        resident.remove(&pid);
+      }
+
+      unheld
+    };
+
+    if unheld {
+      self.eviction_policy().lock().remove(pid);
+    }
+
+    unheld
   }
 
   fn increment_used(&self, len: usize) -> usize {
-    self.2.fetch_add(len, Ordering::SeqCst)
+    self.used.fetch_add(len, Ordering::SeqCst)
   }
 
   fn decrement_used(&self, len: usize) -> usize {
-    self.2.fetch_sub(len, Ordering::SeqCst)
+    self.used.fetch_sub(len, Ordering::SeqCst)
+  }
+
+  //
+  // Takes a frame of the class, evicting victims of the eviction policy while
+  //  the budget is used up or the class pool has no frames left
+  //
+  fn try_alloc_frame(&self, cid: usize) -> Result<usize> {
+    let class = self.try_class_pool(page_class::index_of(cid))?;
+    let len = page_class::size_of(cid);
+    let mut attempts = self.eviction_policy().lock().len() + 1;
+
+    loop {
+      let fits = self.used_bytes() + len <= self.budget();
+      if fits {
+        if let Some(address) = class.alloc() {
+          self.increment_used(len);
+          return Ok(address)
+        }
+      }
+
+      if self.try_reclaim()? > 0 {
+        continue
+      }
+
+      // A full class pool is only helped by evicting a page of the same class
+      let required = if fits { Some(cid) } else { None };
+      if attempts == 0 || !self.try_evict_victim(required)? {
+        return Err(anyhow!("No class {} frame left and no page that can be evicted", cid))
+      }
+
+      attempts -= 1;
+    }
+  }
+
+  // False when the eviction policy has nothing to offer
+  fn try_evict_victim(&self, required: Option<usize>) -> Result<bool> {
+    let victim = self.eviction_policy().lock().victim(&|pid| !self.page_pins().is_pinned(pid) && match required {
+      Some(cid) => self.resident(pid).is_some_and(|(_, resident)| resident == cid),
+      None => true
+    });
+
+    let (pid, hint, frame) = match victim.and_then(|(pid, hint)| Some((pid, hint, self.resident_frame(pid)?))) {
+      Some(victim) => victim,
+      None => return Ok(victim.is_some())
+    };
+
+    let mut page = Page::resident(frame.addr(), frame.cid());
+    let evicted = match WriteGuard::try_latch(&mut page) {
+      Some(page) => self.try_evict_latched(page, frame),
+      None => Ok(false)
+    };
+
+    // Someone is writing to it, holds it or just pinned it so it gets another round instead, as does a page that failed to write back
+    if !matches!(evicted, Ok(true)) {
+      self.eviction_policy().lock().insert(pid, hint);
+    }
+
+    evicted.map(|_| true)
+  }
+
+  // False when the page is pinned or a guard other than the evicting one holds it
+  fn try_evict_latched(&self, page: WriteGuard, frame: Arc<PageFrame>) -> Result<bool> {
+    let pid = PageSWIP::pid(page.swip().value());
+    if Arc::strong_count(&frame) > 2 || !self.page_pins().begin_evict(pid) {
+      return Ok(false)
+    }
+
+    let evicted = self.try_write_back(page, frame);
+    self.page_pins().end_evict(pid);
+    evicted
+  }
+
+  fn try_write_back(&self, page: WriteGuard, frame: Arc<PageFrame>) -> Result<bool> {
+    let pid = PageSWIP::pid(page.swip().value());
+
+    let compressed = match self.compressed_tier() {
+      Some(tier) => self.try_evict_compressed(tier, &page)?,
+      None => false
+    };
+
+    // A clean page that is already in its store is dropped as it is
+    if !compressed && !self.is_stored(&page)? {
+      self.try_flush_latched(&page)?;
+    }
+
+    // A guard fetched the page while it was written back so it stays, clean
+    if !self.untrack_unheld(pid, &frame) {
+      if let Some(tier) = self.compressed_tier().filter(|_| compressed) {
+        if let Some(compressed) = tier.remove(pid) {
+          self.try_release_compressed(compressed)?;
+        }
+      }
+
+      return Ok(false)
+    }
+
+    // The frame goes back to its pool still latched, the next alloc resets it
+    page.swip().clear();
+    std::mem::forget(page);
+    self.try_retire_frame(frame)?;
+
+    match self.compressed_tier() {
+      Some(tier) if compressed => self.try_spill_over_budget(tier).map(|_| true),
+      _ => Ok(true)
+    }
+  }
+
+  // True when the page is clean and its store has a copy of it
+  fn is_stored(&self, page: &WriteGuard) -> Result<bool> {
+    let pid = PageSWIP::pid(page.swip().value());
+    if PageVLDS::dirty(page.vlds().value()) == 1 {
+      return Ok(false)
+    }
+
+    Ok(self.page_tiers().try_store_for(pid)?.location(pid).is_some())
+  }
+
+  fn try_branch<'a>(&'a self, mut page: PageGuard<'a>, txn: usize, fork: bool) -> Result<PageGuard<'a>> {
+    let pid = page.pid();
+
+    {
+      let head = page.try_write()?;
+      let stamp = head.chain().stamp();
+
+      if fork && stamp != txn {
+        return Err(anyhow!("Page {} has no version written by {} to fork", pid, txn))
+      }
+
+      if stamp == txn && !fork {
+        drop(head);
+        return Ok(page)
+      }
+
+      if PageChain::is_uncommitted(stamp) && stamp != txn {
+        return Err(anyhow!("Page {} has an uncommitted version written by {}", pid, stamp))
+      }
+
+      let cid = PageSWIP::cid(head.swip().value());
+      let address = self.try_alloc_frame(cid)?;
+      let past_pid = self.page_id_pool().next()?;
+
+      let past = Page::try_fetch(address, past_pid, cid, |frame| {
+        frame.copy_from_slice(head.bytes());
+        Ok(())
+      });
+
+      let past = match past {
+        Ok(past) => past,
+        Err(err) => {
+          self.try_release_frame(address, cid)?;
+          self.page_id_pool().free(past_pid)?;
+          return Err(err)
+        }
+      };
+
+      let _ = past.vlds().mark_dirty();
+      if !self.page_tiers().is_empty() {
+        self.page_tiers().place(past_pid, self.page_tiers().tier(pid));
+      }
+
+      // Only older snapshots read past versions so they start out cooling
+      self.track(past_pid, address, cid, AccessHint::Sequential);
+
+      head.chain().set_past(past_pid);
+      head.chain().set_stamp(txn);
+      let _ = head.vlds().mark_dirty();
+    }
+
+    self.versions().track(pid);
+    Ok(page)
+  }
+
+  //
+  // Cuts the chain below the newest version every snapshot sees and frees the
+  //  rest, true when the head still has past versions to collect later
+  //
+  fn try_collect_chain(&self, head: usize, horizon: usize) -> Result<(usize, bool)> {
+    let mut pid = head;
+
+    loop {
+      // A freed head can't be walked, its past versions are up to whoever freed it
+      let (past, stamp) = match self.try_chain_of(pid) {
+        Ok(chain) => chain,
+        Err(_) if pid == head => return Ok((0, false)),
+        Err(err) => return Err(err)
+      };
+
+      if past == 0 {
+        return Ok((0, pid != head))
+      }
+
+      if PageChain::is_uncommitted(stamp) || stamp > horizon {
+        pid = past;
+        continue
+      }
+
+      // Only the version the chain is cut at is faulted in
+      {
+        let mut page = self.try_fetch(pid)?;
+        let page = page.try_write()?;
+        page.chain().set_past(0);
+        let _ = page.vlds().mark_dirty();
+      }
+
+      let mut freed = 0;
+      let mut next = past;
+
+      while next != 0 {
+        let (past, _) = self.try_chain_of(next)?;
+        self.try_free_stored(next)?;
+        next = past;
+        freed += 1;
+      }
+
+      return Ok((freed, pid != head))
+    }
+  }
+
+  // The past version and stamp of the page from wherever it is without faulting it into a frame
+  fn try_chain_of(&self, pid: usize) -> Result<(usize, usize)> {
+    let chain_in = |frame: Arc<PageFrame>| {
+      let page = PageGuard::new(Page::resident(frame.addr(), frame.cid())).with_frame(frame);
+      (page.chain().past(), page.chain().stamp())
+    };
+
+    if let Some(frame) = self.resident_frame(pid) {
+      return Ok(chain_in(frame))
+    }
+
+    // Holding the page's fault-in slot keeps it from being read into a frame meanwhile
+    self.prefetcher().begin(pid);
+    let chain = match self.resident_frame(pid) {
+      Some(frame) => Ok(chain_in(frame)),
+      None => self.try_read_chain(pid)
+    };
+
+    self.prefetcher().complete(pid);
+    chain
+  }
+
+  fn try_read_chain(&self, pid: usize) -> Result<(usize, usize)> {
+    let bytes = match self.compressed_tier().map(|tier| tier.try_decompress(pid)).transpose()?.flatten() {
+      Some(bytes) => bytes,
+      None => {
+        let (store, location) = self.page_tiers().try_locate(pid)?;
+        let mut bytes = vec![0u8; location.len()];
+        store.try_read(pid, &mut bytes)?;
+        bytes
+      }
+    };
+
+    // Native endian like every header word
+    let word = |at: usize| bytes.get(at..at + 8).and_then(|word| word.try_into().ok()).map(usize::from_ne_bytes);
+    match (word(SWIP_LEN + VLDS_LEN), word(SWIP_LEN + VLDS_LEN + 8)) {
+      (Some(past), Some(stamp)) => Ok((past, stamp)),
+      _ => Err(anyhow!("Page {} is too short for a header", pid))
+    }
+  }
+
+  fn try_flush_latched(&self, page: &WriteGuard) -> Result<PageLocation> {
+    let pid = PageSWIP::pid(page.swip().value());
+    let location = self.page_tiers().try_store_for(pid)?.try_write(pid, page.bytes())?;
+
+    // We hold the latch so only a concurrent mark can make this fail
+    let _ = page.vlds().mark_clean();
+    self.dirty_pages().clean(pid);
+    Ok(location)
+  }
+
+  // Faults the page into a frame and makes it resident
+  fn try_fault_in(&self, pid: usize, hint: AccessHint) -> Result<Arc<PageFrame>> {
+    let page = self.try_fault_in_page(pid)?;
+    Ok(self.track(pid, page.addr(), PageSWIP::cid(page.swip().value()), hint))
+  }
+
+  fn try_fault_in_page(&self, pid: usize) -> Result<Page<'_>> {
+    if let Some(tier) = self.compressed_tier() {
+      if let Some(frame) = tier.remove(pid) {
+        return self.try_fetch_compressed(tier, pid, frame)
+      }
+    }
+
+    let (store, location) = self.page_tiers().try_locate(pid)?;
+    let cid = location.cid();
+    let address = self.try_alloc_frame(cid)?;
+
+    match Page::try_fetch(address, pid, cid, |frame| store.try_read(pid, frame).map(|_| ())) {
+      Err(err) => {
+        self.try_release_frame(address, cid)?;
+        Err(err)
+      }
+
+      Ok(page) => Ok(page)
+    }
+  }
+
+  // todo: very weird static design choices here
+  // For frames no guard was handed out for
+  fn try_release_frame(&self, addr: usize, cid: usize) -> Result<bool> {
+    self.try_retire_frame(Arc::new(PageFrame::new(addr, cid)))
+  }
+
+  // The frame goes back to its pool once no optimistic reader can still be in it and no guard holds it
+  fn try_retire_frame(&self, frame: Arc<PageFrame>) -> Result<bool> {
+    let pool = self.try_class_pool(page_class::index_of(frame.cid()))?;
+
+    if pool.retire(frame.addr()) {
+      self.epochs().retire(frame);
+      self.try_reclaim()?;
+      Ok(true)
+    } else {
+      Ok(false)
+    }
+  }
+
+  fn try_free_unresident(&self, pid: usize) -> Result<()> {
+    if let Some(frame) = self.compressed_tier().and_then(|tier| tier.remove(pid)) {
+      self.try_release_compressed(frame)?;
+    }
+
+    self.dirty_pages().clean(pid);
+    self.page_tiers().remove(pid);
+    self.page_id_pool().free(pid)
+  }
+
+  fn try_evict_compressed(&self, tier: &CompressedTier, page: &WriteGuard) -> Result<bool> {
+    let swip = page.swip().value();
+    let (pid, cid) = (PageSWIP::pid(swip), PageSWIP::cid(swip));
+
+    let data = match tier.codec().try_compress(page.bytes())? {
+      Some(data) => data,
+      None => return Ok(false)
+    };
+
+    let frame_cid = match tier.frame_class(cid, data.len()) {
+      Some(frame_cid) => frame_cid,
+      None => return Ok(false)
+    };
+
+    // No room in the smaller class means the page goes straight to its store
+    let address = match self.try_class_pool(page_class::index_of(frame_cid))?.alloc() {
+      Some(address) => address,
+      None => return Ok(false)
+    };
+
+    unsafe {
+      std::ptr::copy_nonoverlapping(data.as_ptr(), address as *mut u8, data.len());
+    }
+
+    self.increment_used(page_class::size_of(frame_cid));
+    let dirty = PageVLDS::dirty(page.vlds().value()) == 1;
+    tier.insert(pid, CompressedFrame::new(address, frame_cid, cid, data.len(), dirty));
+
+    Ok(true)
+  }
+
+  fn try_fetch_compressed(&self, tier: &CompressedTier, pid: usize, frame: CompressedFrame) -> Result<Page<'_>> {
+    let cid = frame.page_cid();
+    let address = match self.try_alloc_frame(cid) {
+      Ok(address) => address,
+      Err(err) => {
+        tier.insert(pid, frame);
+        return Err(err)
+      }
+    };
+
+    match Page::try_fetch(address, pid, cid, |dst| tier.codec().try_decompress(frame.data(), dst)) {
+      Err(err) => {
+        self.try_release_frame(address, cid)?;
+        tier.insert(pid, frame);
+        Err(err)
+      }
+
+      Ok(page) => {
+        // The page never made it to a store so it is still dirty
+        if frame.is_dirty() {
+          let _ = page.vlds().mark_dirty();
+        }
+
+        self.try_release_compressed(frame)?;
+        Ok(page)
+      }
+    }
+  }
+
+  fn try_spill_over_budget(&self, tier: &CompressedTier) -> Result<()> {
+    while let Some((pid, frame)) = tier.pop_over_budget() {
+      self.try_spill_frame(tier, pid, frame)?;
+    }
+
+    Ok(())
+  }
+
+  fn try_spill_frame(&self, tier: &CompressedTier, pid: usize, frame: CompressedFrame) -> Result<()> {
+    if frame.is_dirty() {
+      let mut data = vec![0u8; page_class::size_of(frame.page_cid())];
+      let written = tier.codec().try_decompress(frame.data(), &mut data)
+        .and_then(|_| self.page_tiers().try_store_for(pid)?.try_write(pid, &data));
+
+      if let Err(err) = written {
+        tier.insert(pid, frame);
+        return Err(err)
+      }
+
+      self.dirty_pages().clean(pid);
+    }
+
+    self.try_release_compressed(frame)
+  }
+
+  fn try_release_compressed(&self, frame: CompressedFrame) -> Result<()> {
+    if self.try_class_pool(page_class::index_of(frame.frame_cid()))?.free(frame.addr()) {
+      self.decrement_used(page_class::size_of(frame.frame_cid()));
+    }
+
+    Ok(())
   }
 
   fn try_class_pool(&self, idx: usize) -> Result<&AddressPool> {
-    match self.0.get(idx) {
+    match self.pools.get(idx) {
       Some(pool) => Ok(pool),
       None => Err(anyhow!("Page size class not found at {}", idx))
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ PageCodec, PageStore };
+
+  #[test]
+  fn test_try_evict_and_fetch_from_tier() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-tiers-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new()
+      .with(StorageTier::Index, PageStore::try_open(dir.join("index"))?)
+      .with(StorageTier::Local, PageStore::try_open(dir.join("local"))?);
+
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?;
+    let mut page = pages.try_alloc_in(64, StorageTier::Index)?;
+
+    let pid = {
+      let mut page = page.try_write()?;
+      page.write(0, 3, &mut Cursor::new(vec![1u8, 2u8, 3u8]))?;
+      PageSWIP::pid(page.swip().value())
+    };
+
+    pages.try_evict(page)?;
+    assert_eq!(0, pages.used_bytes());
+    assert!(dir.join("index").join("class-12").exists());
+    assert!(!dir.join("local").join("class-12").exists());
+
+    let mut page = pages.try_fetch(pid)?;
+    let mut data = vec![];
+    page.try_write()?.read(0, 3, &mut data)?;
+    assert_eq!(vec![1u8, 2u8, 3u8], data);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_pin_blocks_eviction() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-pins-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?
+      .with_pin_limit(page_class::size_of(MIN_CLASS_ID));
+
+    let (first, second) = (pages.try_alloc(64)?.pid(), pages.try_alloc(64)?.pid());
+    let pinned = pages.try_pin(first)?;
+
+    assert!(pages.try_evict(pinned.guard()).is_err());
+    assert!(pages.try_free(pinned.guard()).is_err());
+    assert!(pages.try_pin(second).is_err());
+    assert_eq!(page_class::size_of(MIN_CLASS_ID), pages.pinned_bytes());
+
+    drop(pinned);
+    assert_eq!(0, pages.pinned_bytes());
+    pages.try_evict(pages.try_fetch(first)?)?;
+    assert!(!pages.is_resident(first));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_evict_skips_held_pages() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-held-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?;
+
+    let mut held = pages.try_alloc(64)?;
+    let pid = held.pid();
+    held.try_write()?.write(0, 3, &mut Cursor::new(vec![1u8, 2u8, 3u8]))?;
+
+    assert!(pages.try_evict(pages.try_fetch(pid)?).is_err());
+    assert!(pages.is_resident(pid));
+
+    let mut data = vec![];
+    held.try_write()?.read(0, 3, &mut data)?;
+    assert_eq!(vec![1u8, 2u8, 3u8], data);
+
+    pages.try_evict(held)?;
+    assert!(!pages.is_resident(pid));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_compressed_tier_keeps_and_spills_pages() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-compressed-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // Room for a single compressed frame
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?
+      .with_compressed_tier(CompressedTier::new(PageCodec::Lz4, page_class::size_of(MIN_CLASS_ID)));
+
+    let len = 2 * page_class::size_of(MIN_CLASS_ID);
+    let data: Vec<u8> = (0..len).map(|i| (i / 512) as u8).collect();
+    let mut pids = vec![];
+
+    for _ in 0..2 {
+      let mut page = pages.try_alloc(len as u32)?;
+      page.try_write()?.write(0, len, &mut Cursor::new(&data))?;
+      pids.push(page.pid());
+      pages.try_evict(page)?;
+
+      assert_eq!(page_class::size_of(MIN_CLASS_ID), pages.used_bytes());
+    }
+
+    // The older frame was spilled to the store to make room for the newer one
+    let tier = pages.compressed_tier().ok_or_else(|| anyhow!("No compressed tier"))?;
+    let store = pages.store(StorageTier::Local).ok_or_else(|| anyhow!("No local store"))?;
+    assert!(!tier.contains(pids[0]) && store.location(pids[0]).is_some());
+    assert!(tier.contains(pids[1]) && store.location(pids[1]).is_none());
+
+    for pid in pids {
+      let mut read = vec![];
+      pages.try_fetch(pid)?.try_write()?.read(0, len, &mut read)?;
+      assert_eq!(data, read);
+    }
+
+    assert!(tier.is_empty());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_resize_keeps_pid_and_data() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let mut page = pages.try_alloc(64)?;
+    let pid = page.pid();
+
+    page.try_write()?.write(0, 3, &mut Cursor::new(vec![1u8, 2u8, 3u8]))?;
+    let used = pages.used_bytes();
+
+    let mut page = pages.try_resize(page, 2 * page_class::size_of(MIN_CLASS_ID) as u32)?;
+    assert_eq!(pid, page.pid());
+    assert_eq!(used * 4, pages.used_bytes());
+
+    let mut data = vec![];
+    page.try_write()?.read(0, 3, &mut data)?;
+    assert_eq!(vec![1u8, 2u8, 3u8], data);
+
+    // The old frame isn't reused while a guard still holds it, the guard finds it cleared
+    let mut held = pages.try_fetch(pid)?;
+    let mut worker = pages.epochs().register();
+    let swizzled = Swip::swizzled(pages.resident(pid).map(|(addr, _)| addr).unwrap_or_default());
+    assert!(pages.swizzled(swizzled, pid, &worker.enter()).is_some());
+
+    let mut page = pages.try_resize(page, 64)?;
+    assert_eq!(used * 5, pages.used_bytes());
+    assert!(held.try_write().is_err());
+    assert!(pages.swizzled(swizzled, pid, &worker.enter()).is_none());
+    let resized = Swip::swizzled(pages.resident(pid).map(|(addr, _)| addr).unwrap_or_default());
+    assert!(pages.swizzled(resized, pid, &worker.enter()).is_some());
+    drop(worker);
+
+    drop(held);
+    pages.try_reclaim()?;
+    assert_eq!(used, pages.used_bytes());
+
+    let mut data = vec![];
+    page.try_write()?.read(0, 3, &mut data)?;
+    assert_eq!(vec![1u8, 2u8, 3u8], data);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_cow_chains_versions() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let txn = crate::TXN_ID_BASE + 1;
+
+    let mut page = pages.try_alloc(64)?;
+    let pid = page.pid();
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![1u8]))?;
+
+    let mut page = pages.try_cow(page, txn)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![2u8]))?;
+    assert!(pages.try_cow(pages.try_fetch(pid)?, txn + 1).is_err());
+
+    let read = |snapshot: usize, txn: usize| -> Result<u8> {
+      let mut page = pages.try_fetch_visible(pid, snapshot, txn)?.ok_or_else(|| anyhow!("Nothing visible"))?;
+      let mut data = vec![];
+      page.try_write()?.read(0, 1, &mut data)?;
+      Ok(data[0])
+    };
+
+    assert_eq!(2, read(0, txn)?);
+    assert_eq!(1, read(0, txn + 1)?);
+
+    // Committing at 5 makes the new version visible from snapshot 5 on
+    page.chain().set_stamp(5);
+    assert_eq!(1, read(4, txn + 1)?);
+    assert_eq!(2, read(5, txn + 1)?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_collect_versions_below_horizon() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let mut page = pages.try_alloc(64)?;
+    let pid = page.pid();
+
+    for time in 1..=3 {
+      page = pages.try_cow(page, crate::TXN_ID_BASE + time)?;
+      page.chain().set_stamp(time);
+    }
+
+    let used = pages.used_bytes();
+    pages.versions().register(2);
+    assert_eq!(2, pages.try_collect_versions(8)?);
+    assert_eq!(1, pages.versions().pending());
+    assert_eq!(used / 2, pages.used_bytes());
+
+    pages.versions().release(2);
+    pages.versions().advance(3);
+    assert_eq!(1, pages.try_collect_versions(8)?);
+    assert_eq!(0, pages.versions().pending());
+    assert_eq!(0, pages.try_fetch(pid)?.chain().past());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_collect_versions_frees_evicted_versions_in_place() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-versions-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?;
+    let mut page = pages.try_alloc(64)?;
+
+    for time in 1..=3 {
+      page = pages.try_cow(page, crate::TXN_ID_BASE + time)?;
+      page.chain().set_stamp(time);
+    }
+
+    let (mut past, mut pid) = (vec![], page.chain().past());
+    while pid != 0 {
+      let version = pages.try_fetch(pid)?;
+      past.push(pid);
+      pid = version.chain().past();
+      pages.try_evict(version)?;
+    }
+
+    // Everything below the version a snapshot at 2 still reads is freed from the store without a frame
+    let store = pages.store(StorageTier::Local).ok_or_else(|| anyhow!("No local store"))?;
+    pages.versions().register(2);
+    assert_eq!(2, pages.try_collect_versions(8)?);
+    assert!(!pages.is_resident(past[1]) && !pages.is_resident(past[2]));
+    assert!(store.location(past[1]).is_none() && store.location(past[2]).is_none());
+    assert_eq!(0, pages.try_fetch(past[0])?.chain().past());
+
+    drop(page);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_reads_of_a_reused_frame_do_not_validate() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let page = pages.try_alloc(64)?;
+    let (addr, cid, pid) = (page.page().addr(), PageSWIP::cid(page.page().swip().value()), page.pid());
+    let mut worker = pages.epochs().register();
+
+    {
+      let epoch = worker.enter();
+      let frame = Page::resident(addr, cid);
+      let read = pages.optimistic(&frame, &epoch).expect("nobody is writing the page");
+      assert!(read.is_valid());
+
+      // The frame holds the same PID again as after a free and a fault-in, nobody wrote it in between
+      Page::try_alloc(addr, pid, cid)?;
+      assert!(!read.is_valid());
+      assert!(read.try_read(0, 1, &mut vec![])?.is_none());
+    }
+
+    pages.try_free(page)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_try_free_waits_for_readers() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let (freed, other) = (pages.try_alloc(64)?, pages.try_alloc(64)?);
+    let used = pages.used_bytes();
+    let mut worker = pages.epochs().register();
+
+    {
+      let epoch = worker.enter();
+      let reader = pages.try_fetch(freed.pid())?;
+      let read = pages.optimistic(reader.page(), &epoch).expect("nobody is writing the page");
+      assert!(read.try_read(0, 1, &mut vec![])?.is_some());
+
+      // Another manager's epoch doesn't keep this manager's frames
+      let others = PageManager::try_new(2usize.pow(31))?;
+      let mut stranger = others.epochs().register();
+      assert!(pages.optimistic(reader.page(), &stranger.enter()).is_none());
+
+      // The reader could still be in the frame so it isn't handed out again
+      pages.try_free(freed)?;
+      assert_eq!(1, pages.epochs().retired_len());
+      assert_eq!(used, pages.used_bytes());
+
+      // The freed frame stays latched, readers restart instead of waiting on it
+      assert!(read.try_read(0, 1, &mut vec![])?.is_none());
+      assert!(pages.optimistic(reader.page(), &epoch).is_none());
+    }
+
+    assert!(worker.is_idle());
+    pages.try_free(other)?;
+    assert_eq!(0, pages.epochs().retired_len());
+    assert_eq!(0, pages.used_bytes());
+
+    Ok(())
+  }
+}
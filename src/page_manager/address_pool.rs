@@ -20,16 +20,22 @@ use addr_pool::*;
 pub struct AddressPool(usize, Arc<MmapMut>, Arc<Mutex<AddrPool>>);
 
 impl AddressPool {
-  fn cid(&self) -> usize {
+  pub fn cid(&self) -> usize {
     self.0
   }
 
-  fn data(&self) -> &MmapMut {
-    &self.1.as_ref()
+  pub fn data(&self) -> &MmapMut {
+    self.1.as_ref()
   }
 
   fn pools(&self) -> &Mutex<AddrPool> {
-    &self.2.as_ref()
+    self.2.as_ref()
+  }
+
+  // True for the address of any frame of the pool, in use or not
+  pub fn contains(&self, addr: usize) -> bool {
+    let base = self.data().as_ptr() as usize;
+    (base..base + self.data().len()).contains(&addr) && (addr - base).is_multiple_of(2usize.pow(self.cid() as u32))
   }
 
   pub fn alloc(&self) -> Option<usize> {
@@ -40,6 +46,14 @@ impl AddressPool {
     self.pools().lock().free(addr)
   }
 
+  pub fn retire(&self, addr: usize) -> bool {
+    self.pools().lock().retire(addr)
+  }
+
+  pub fn reclaim(&self, addr: usize) {
+    self.pools().lock().reclaim(addr)
+  }
+
   pub fn try_new(pool_size: usize, cid: usize) -> Result<Self> {
     // TODO: use page_class::size_of(cid) and page_class::size_of(MAX_CLASS_ID)
     let frame_size = 2usize.pow(cid as u32);
@@ -53,7 +67,7 @@ impl AddressPool {
       return Err(anyhow!("Page pool size must be greater than {} bytes", max_frame_size))
     }
 
-    if pool_size % frame_size != 0 {
+    if !pool_size.is_multiple_of(frame_size) {
       return Err(anyhow!("Page pool size must be divisible by page size: {} / {}", pool_size, frame_size))
     }
 
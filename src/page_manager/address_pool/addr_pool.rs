@@ -28,6 +28,15 @@ impl AddrPool {
     }
   }
 
+  // Takes the frame out of use without making it available yet
+  pub fn retire(&mut self, addr: usize) -> bool {
+    self.used_mut().remove(addr).is_some()
+  }
+
+  pub fn reclaim(&mut self, addr: usize) {
+    self.free_mut().push_front(addr);
+  }
+
   pub fn free(&mut self, addr: usize) -> bool {
     if let Some(addr) = self.used_mut().remove(addr) {
       self.free_mut().push_front(addr);
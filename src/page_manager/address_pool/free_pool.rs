@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct FreePool(VecDeque<usize>);
 
 impl FreePool {
@@ -8,17 +8,11 @@ impl FreePool {
     self.0.pop_front()
   }
 
-  pub fn push_back(&mut self, address: usize) -> () {
+  pub fn push_back(&mut self, address: usize) {
     self.0.push_back(address)
   }
 
-  pub fn push_front(&mut self, address: usize) -> () {
+  pub fn push_front(&mut self, address: usize) {
     self.0.push_front(address)
   }
 }
-
-impl Default for FreePool {
-  fn default() -> Self {
-    Self(Default::default())
-  }
-}
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::collections::{ BTreeSet };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct UsedPool(BTreeSet<usize>);
 
 impl UsedPool {
@@ -16,9 +16,3 @@ impl UsedPool {
     }
   }
 }
-
-impl Default for UsedPool {
-  fn default() -> Self {
-    Self(Default::default())
-  }
-}
\ No newline at end of file
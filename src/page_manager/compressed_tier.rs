@@ -0,0 +1,151 @@
+use anyhow::Result;
+
+use parking_lot::Mutex;
+
+use std::collections::{
+  HashMap, VecDeque
+};
+
+use crate::{
+  MAX_CLASS_ID, MIN_CLASS_ID, page_class, PageCodec
+};
+
+//
+// Evicted pages that compress into a smaller class are kept here, in frames
+//  taken from the smaller class pools, before they ever go to a page store
+//
+
+// Address, frame class, page class, compressed length, dirty
+#[derive(Clone, Copy, Debug)]
+pub struct CompressedFrame(usize, usize, usize, usize, bool);
+
+impl CompressedFrame {
+  pub fn addr(&self) -> usize {
+    self.0
+  }
+
+  pub fn frame_cid(&self) -> usize {
+    self.1
+  }
+
+  pub fn page_cid(&self) -> usize {
+    self.2
+  }
+
+  pub fn len(&self) -> usize {
+    self.3
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn is_dirty(&self) -> bool {
+    self.4
+  }
+
+  pub fn data(&self) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(self.addr() as *const u8, self.len()) }
+  }
+
+  pub fn new(addr: usize, frame_cid: usize, page_cid: usize, len: usize, dirty: bool) -> Self {
+    Self(addr, frame_cid, page_cid, len, dirty)
+  }
+}
+
+#[derive(Debug, Default)]
+struct CompressedFrames(HashMap<usize, CompressedFrame>, VecDeque<usize>, usize);
+
+#[derive(Debug)]
+pub struct CompressedTier(PageCodec, usize, Mutex<CompressedFrames>);
+
+impl CompressedTier {
+  pub fn codec(&self) -> PageCodec {
+    self.0
+  }
+
+  // Bytes of frames the tier may hold before it spills to the page stores
+  pub fn budget(&self) -> usize {
+    self.1
+  }
+
+  pub fn used_bytes(&self) -> usize {
+    self.2.lock().2
+  }
+
+  pub fn len(&self) -> usize {
+    self.2.lock().0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn contains(&self, pid: usize) -> bool {
+    self.2.lock().0.contains_key(&pid)
+  }
+
+  // A copy of the page without taking it out of the tier
+  pub fn try_decompress(&self, pid: usize) -> Result<Option<Vec<u8>>> {
+    let frames = self.2.lock();
+    let frame = match frames.0.get(&pid) {
+      Some(frame) => frame,
+      None => return Ok(None)
+    };
+
+    let mut bytes = vec![0u8; page_class::size_of(frame.page_cid())];
+    self.codec().try_decompress(frame.data(), &mut bytes)?;
+    Ok(Some(bytes))
+  }
+
+  // The smallest class that holds the compressed bytes if it is smaller than the page class
+  pub fn frame_class(&self, page_cid: usize, len: usize) -> Option<usize> {
+    let cid = (MIN_CLASS_ID..=MAX_CLASS_ID).find(|cid| page_class::size_of(*cid) >= len)?;
+    if cid < page_cid { Some(cid) } else { None }
+  }
+
+  pub fn insert(&self, pid: usize, frame: CompressedFrame) {
+    let mut frames = self.2.lock();
+    frames.2 += page_class::size_of(frame.frame_cid());
+    frames.1.push_back(pid);
+    frames.0.insert(pid, frame);
+  }
+
+  pub fn remove(&self, pid: usize) -> Option<CompressedFrame> {
+    let mut frames = self.2.lock();
+    let frame = frames.0.remove(&pid)?;
+
+    frames.2 -= page_class::size_of(frame.frame_cid());
+    if let Some(idx) = frames.1.iter().position(|queued| *queued == pid) {
+      frames.1.remove(idx);
+    }
+
+    Some(frame)
+  }
+
+  // Oldest frame first once the tier is over its budget
+  pub fn pop_over_budget(&self) -> Option<(usize, CompressedFrame)> {
+    let mut frames = self.2.lock();
+
+    while frames.2 > self.budget() {
+      let pid = frames.1.pop_front()?;
+      if let Some(frame) = frames.0.remove(&pid) {
+        frames.2 -= page_class::size_of(frame.frame_cid());
+        return Some((pid, frame))
+      }
+    }
+
+    None
+  }
+
+  pub fn drain(&self) -> Vec<(usize, CompressedFrame)> {
+    let mut frames = self.2.lock();
+    let pids: Vec<usize> = frames.1.drain(..).collect();
+    frames.2 = 0;
+    pids.into_iter().filter_map(|pid| frames.0.remove(&pid).map(|frame| (pid, frame))).collect()
+  }
+
+  pub fn new(codec: PageCodec, budget: usize) -> Self {
+    Self(codec, budget, Mutex::new(CompressedFrames::default()))
+  }
+}
@@ -0,0 +1,58 @@
+use parking_lot::Mutex;
+
+use std::collections::HashMap;
+
+//
+// The dirty page table from docs/acid/checkpoints.md. The dirty bit in VLDS
+//  says that a page is dirty, this says since which LSN: a page is added
+//  with the LSN of the first logged write after it was last flushed and
+//  leaves once it is written to its store
+//
+// Writers add the page after the write it logged is in the page, so a flush
+//  that raced ahead of the write can only leave an entry behind and never
+//  drop one that redo still needs
+//
+
+// PID to recovery LSN
+#[derive(Debug, Default)]
+pub struct DirtyPages(Mutex<HashMap<usize, usize>>);
+
+impl DirtyPages {
+  pub fn len(&self) -> usize {
+    self.0.lock().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn rec_lsn(&self, pid: usize) -> Option<usize> {
+    self.0.lock().get(&pid).copied()
+  }
+
+  // Keeps the older LSN when the page is already dirty
+  pub fn mark(&self, pid: usize, lsn: usize) {
+    let mut pages = self.0.lock();
+    let rec_lsn = pages.entry(pid).or_insert(lsn);
+    *rec_lsn = (*rec_lsn).min(lsn);
+  }
+
+  pub fn clean(&self, pid: usize) {
+    self.0.lock().remove(&pid);
+  }
+
+  pub fn min_rec_lsn(&self) -> Option<usize> {
+    self.0.lock().values().min().copied()
+  }
+
+  // Copy of the table, oldest recovery LSN first
+  pub fn entries(&self) -> Vec<(usize, usize)> {
+    let mut entries: Vec<(usize, usize)> = self.0.lock().iter().map(|(pid, lsn)| (*pid, *lsn)).collect();
+    entries.sort_unstable_by_key(|(pid, lsn)| (*lsn, *pid));
+    entries
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
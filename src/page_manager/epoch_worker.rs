@@ -0,0 +1,70 @@
+use std::sync::{
+  Arc,
+  atomic::{ AtomicUsize, Ordering }
+};
+
+use crate::{ Epochs, EPOCH_IDLE };
+
+// A thread that reads pages optimistically, frames stay allocated while it is in an epoch
+#[derive(Debug)]
+pub struct EpochWorker<'a>(&'a Epochs, Arc<AtomicUsize>);
+
+impl<'a> Drop for EpochWorker<'a> {
+  fn drop(&mut self) {
+    self.0.deregister(&self.1);
+  }
+}
+
+impl<'a> EpochWorker<'a> {
+  pub fn epoch(&self) -> usize {
+    self.1.load(Ordering::SeqCst)
+  }
+
+  pub fn is_idle(&self) -> bool {
+    self.epoch() == EPOCH_IDLE
+  }
+
+  //
+  // Held around optimistic reads, taking &mut keeps guards from nesting.
+  //  The global epoch can move on between reading and publishing it, which
+  //  would let frames retired in between be reclaimed under us, so publish
+  //  until what we published is still the global epoch
+  //
+  pub fn enter(&mut self) -> EpochGuard<'_> {
+    let mut epoch = self.0.epoch();
+
+    loop {
+      self.1.store(epoch, Ordering::SeqCst);
+
+      match self.0.epoch() {
+        current if current == epoch => return EpochGuard(self.0, &self.1),
+        current => epoch = current
+      }
+    }
+  }
+
+  pub fn new(epochs: &'a Epochs, worker: Arc<AtomicUsize>) -> Self {
+    Self(epochs, worker)
+  }
+}
+
+// The epochs the worker is registered with and the worker's epoch
+#[derive(Debug)]
+pub struct EpochGuard<'w>(&'w Epochs, &'w AtomicUsize);
+
+impl<'w> EpochGuard<'w> {
+  pub fn epoch(&self) -> usize {
+    self.1.load(Ordering::SeqCst)
+  }
+
+  // Frames retired by other page managers aren't kept by this guard
+  pub fn is_of(&self, epochs: &Epochs) -> bool {
+    std::ptr::eq(self.0, epochs)
+  }
+}
+
+impl<'w> Drop for EpochGuard<'w> {
+  fn drop(&mut self) {
+    self.1.store(EPOCH_IDLE, Ordering::SeqCst);
+  }
+}
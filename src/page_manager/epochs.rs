@@ -0,0 +1,85 @@
+use parking_lot::Mutex;
+
+use std::{
+  collections::VecDeque,
+  sync::{
+    Arc,
+    atomic::{ AtomicUsize, Ordering }
+  }
+};
+
+use crate::{ EpochWorker, PageFrame };
+
+// The epoch of a registered worker that isn't reading anything
+pub const EPOCH_IDLE: usize = usize::MAX;
+
+//
+// Epoch based reclamation: optimistic readers don't latch the frames they
+//  read so a freed frame can't be handed out again while one of them might
+//  still be copying out of it. Workers enter the global epoch around their
+//  reads, every released frame is retired with the epoch it became
+//  unreachable in and goes back to its pool once every worker has moved past
+//  that epoch and no page guard holds on to it anymore
+//
+
+// Global epoch, the epoch of every registered worker and retired frames oldest first
+#[derive(Debug, Default)]
+pub struct Epochs(AtomicUsize, Mutex<Vec<Arc<AtomicUsize>>>, Mutex<VecDeque<(usize, Arc<PageFrame>)>>);
+
+impl Epochs {
+  pub fn epoch(&self) -> usize {
+    self.0.load(Ordering::SeqCst)
+  }
+
+  pub fn workers_len(&self) -> usize {
+    self.1.lock().len()
+  }
+
+  pub fn retired_len(&self) -> usize {
+    self.2.lock().len()
+  }
+
+  // The oldest epoch a worker is still reading in
+  pub fn oldest(&self) -> usize {
+    self.1.lock().iter().map(|worker| worker.load(Ordering::SeqCst)).min().unwrap_or(EPOCH_IDLE)
+  }
+
+  pub fn register(&self) -> EpochWorker<'_> {
+    let worker = Arc::new(AtomicUsize::new(EPOCH_IDLE));
+    self.1.lock().push(worker.clone());
+
+    EpochWorker::new(self, worker)
+  }
+
+  pub fn deregister(&self, worker: &Arc<AtomicUsize>) {
+    self.1.lock().retain(|registered| !Arc::ptr_eq(registered, worker));
+  }
+
+  // Only call once the frame can't be reached anymore, readers entering later never see it
+  pub fn retire(&self, frame: Arc<PageFrame>) {
+    let epoch = self.0.fetch_add(1, Ordering::SeqCst);
+    self.2.lock().push_back((epoch, frame));
+  }
+
+  // Frames (address, class) no worker can still be reading and no guard still holds
+  pub fn reclaim(&self) -> Vec<(usize, usize)> {
+    let oldest = self.oldest();
+    let mut retired = self.2.lock();
+    let mut frames = vec![];
+
+    retired.retain(|(epoch, frame)| {
+      let reclaimed = *epoch < oldest && Arc::strong_count(frame) == 1;
+      if reclaimed {
+        frames.push((frame.addr(), frame.cid()));
+      }
+
+      !reclaimed
+    });
+
+    frames
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
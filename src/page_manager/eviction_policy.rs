@@ -0,0 +1,89 @@
+mod clock;
+mod cooling_queue;
+mod lru_k;
+mod two_queue;
+
+use std::fmt::Debug;
+
+use crate::AccessHint;
+
+pub use clock::*;
+pub use cooling_queue::*;
+pub use lru_k::*;
+pub use two_queue::*;
+
+//
+// Decides which resident page is evicted next, PageManager tells the policy
+//  about every page that comes in, is touched or goes away and asks it for
+//  a victim whenever it runs out of frames (see README)
+//
+// Every policy honors access hints, a page a scan read is a better victim
+//  than any page that wasn't and WillNeed pages are only given up when no
+//  other page is accepted
+//
+
+pub trait EvictionPolicy: Debug + Send {
+  // Pages the policy is tracking
+  fn len(&self) -> usize;
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn insert(&mut self, pid: usize, hint: AccessHint);
+
+  fn access(&mut self, pid: usize, hint: AccessHint);
+
+  fn remove(&mut self, pid: usize);
+
+  // Stops tracking and returns the page to evict next out of those accepted, with the hint it was tracked with
+  fn victim(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<(usize, AccessHint)>;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn policies() -> Vec<Box<dyn EvictionPolicy>> {
+    vec![
+      Box::new(CoolingQueue::new()),
+      Box::new(Clock::new()),
+      Box::new(TwoQueue::new()),
+      Box::new(LruK::new(2))
+    ]
+  }
+
+  #[test]
+  fn test_scans_are_evicted_before_hot_pages() {
+    for mut policy in policies() {
+      for pid in (1..9).step_by(2) {
+        policy.insert(pid, AccessHint::Normal);
+        policy.access(pid, AccessHint::Normal);
+      }
+
+      for pid in (101..141).step_by(2) {
+        policy.insert(pid, AccessHint::Sequential);
+      }
+
+      for _ in 0..20 {
+        let (pid, hint) = policy.victim(&|_| true).unwrap();
+        assert_eq!(AccessHint::Sequential, hint, "{:?}", policy);
+        assert!(pid > 100, "{:?} evicted hot page {}", policy, pid);
+      }
+
+      policy.insert(9, AccessHint::WillNeed);
+      policy.insert(11, AccessHint::DontNeed);
+      assert_eq!(Some((11, AccessHint::DontNeed)), policy.victim(&|_| true), "{:?}", policy);
+      assert_eq!(Some((3, AccessHint::Normal)), policy.victim(&|pid| pid == 3), "{:?}", policy);
+
+      let mut rest = vec![];
+      while let Some((pid, _)) = policy.victim(&|_| true) {
+        rest.push(pid);
+      }
+
+      assert_eq!(Some(&9), rest.last(), "{:?}", policy);
+      assert_eq!(4, rest.len());
+      assert_eq!(0, policy.len());
+    }
+  }
+}
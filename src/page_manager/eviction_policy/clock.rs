@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::{ AccessHint, EvictionPolicy };
+
+//
+// CLOCK: the hand sweeps a ring of pages, clearing reference bits until it
+//  finds a page nobody touched since the last sweep
+//
+// Scanned pages never get their bit set, DontNeed pages go before the hand
+//  moves at all and the hand passes WillNeed pages by
+//
+
+// Ring of pages and their reference bits, hand, positions in the ring and non-normal hints
+#[derive(Debug, Default)]
+pub struct Clock(Vec<(usize, bool)>, usize, HashMap<usize, usize>, HashMap<usize, AccessHint>);
+
+impl Clock {
+  pub fn hint(&self, pid: usize) -> AccessHint {
+    self.3.get(&pid).copied().unwrap_or_default()
+  }
+
+  pub fn is_referenced(&self, pid: usize) -> bool {
+    self.2.get(&pid).is_some_and(|idx| self.0[*idx].1)
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Private Helpers
+
+  fn set_referenced(&mut self, pid: usize, referenced: bool) {
+    if let Some(idx) = self.2.get(&pid) {
+      self.0[*idx].1 = referenced;
+    }
+  }
+
+  fn sweep(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<usize> {
+    // Two turns clear every bit, a third finds nothing new
+    for _ in 0..self.0.len() * 2 + 1 {
+      if self.0.is_empty() {
+        return None
+      }
+
+      if self.1 >= self.0.len() {
+        self.1 = 0;
+      }
+
+      let (pid, referenced) = self.0[self.1];
+      if !accept(pid) || self.hint(pid) == AccessHint::WillNeed {
+        self.1 += 1;
+      } else if referenced {
+        self.0[self.1].1 = false;
+        self.1 += 1;
+      } else {
+        return Some(pid)
+      }
+    }
+
+    None
+  }
+}
+
+impl EvictionPolicy for Clock {
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn insert(&mut self, pid: usize, hint: AccessHint) {
+    if !self.2.contains_key(&pid) {
+      self.2.insert(pid, self.0.len());
+      self.0.push((pid, false));
+    }
+
+    self.access(pid, hint);
+  }
+
+  fn access(&mut self, pid: usize, hint: AccessHint) {
+    if !self.2.contains_key(&pid) {
+      return
+    }
+
+    match hint {
+      AccessHint::Normal => {
+        self.3.remove(&pid);
+        self.set_referenced(pid, true);
+      }
+
+      AccessHint::Sequential => {
+        self.3.insert(pid, hint);
+      }
+
+      AccessHint::WillNeed => {
+        self.3.insert(pid, hint);
+        self.set_referenced(pid, true);
+      }
+
+      AccessHint::DontNeed => {
+        self.3.insert(pid, hint);
+        self.set_referenced(pid, false);
+      }
+    }
+  }
+
+  fn remove(&mut self, pid: usize) {
+    self.3.remove(&pid);
+
+    if let Some(idx) = self.2.remove(&pid) {
+      self.0.swap_remove(idx);
+      if let Some((moved, _)) = self.0.get(idx) {
+        self.2.insert(*moved, idx);
+      }
+    }
+  }
+
+  fn victim(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<(usize, AccessHint)> {
+    let pid = self.3.iter().find(|(pid, hint)| **hint == AccessHint::DontNeed && accept(**pid)).map(|(pid, _)| *pid)
+      .or_else(|| self.sweep(accept))
+      .or_else(|| self.0.iter().map(|(pid, _)| *pid).find(|pid| accept(*pid)))?;
+
+    let hint = self.hint(pid);
+    self.remove(pid);
+    Some((pid, hint))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_referenced_pages_get_a_second_chance() {
+    let mut clock = Clock::new();
+    for pid in (1..9).step_by(2) {
+      clock.insert(pid, AccessHint::Normal);
+    }
+
+    // Every page was just referenced, the first sweep clears them all and takes the first
+    assert_eq!(Some((1, AccessHint::Normal)), clock.victim(&|_| true));
+    assert!(!clock.is_referenced(7));
+
+    // The page under the hand was touched again so it is passed over once
+    clock.access(7, AccessHint::Normal);
+    assert_eq!(Some((3, AccessHint::Normal)), clock.victim(&|_| true));
+    assert!(!clock.is_referenced(7));
+    assert_eq!(2, clock.len());
+  }
+
+  #[test]
+  fn test_untracked_pages_are_not_hinted() {
+    let mut clock = Clock::new();
+    clock.insert(1, AccessHint::Normal);
+
+    // An access that races with the page's eviction
+    clock.access(3, AccessHint::DontNeed);
+    assert_eq!(AccessHint::Normal, clock.hint(3));
+    assert_eq!(Some((1, AccessHint::Normal)), clock.victim(&|_| true));
+    assert_eq!(None, clock.victim(&|_| true));
+  }
+}
@@ -0,0 +1,216 @@
+use std::{
+  collections::{ HashMap, HashSet, VecDeque },
+  time::{ SystemTime, UNIX_EPOCH }
+};
+
+use crate::{ AccessHint, EvictionPolicy };
+
+// Share of the resident pages kept cooling ahead of eviction
+pub const COOLING_PERCENT: usize = 10;
+
+//
+// The fridge from NOTES.md 2021-12-22: hot pages are picked at random and put
+//  at the back of a FIFO cooling queue, a cooling page that is touched again
+//  gets a second chance and victims are taken from the front of the queue
+//
+// Access hints change where a page goes, a scan cools its pages as it reads
+//  them instead of pushing hot index pages into the queue at random
+//
+
+// Hot pages, their positions, cooling FIFO, cooling set, non-normal hints and random state
+#[derive(Debug)]
+pub struct CoolingQueue(Vec<usize>, HashMap<usize, usize>, VecDeque<usize>, HashSet<usize>, HashMap<usize, AccessHint>, u64);
+
+impl CoolingQueue {
+  pub fn cooling_len(&self) -> usize {
+    self.2.len()
+  }
+
+  pub fn is_cooling(&self, pid: usize) -> bool {
+    self.3.contains(&pid)
+  }
+
+  pub fn hint(&self, pid: usize) -> AccessHint {
+    self.4.get(&pid).copied().unwrap_or_default()
+  }
+
+  pub fn new() -> Self {
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_nanos() as u64);
+    Self(vec![], HashMap::new(), VecDeque::new(), HashSet::new(), HashMap::new(), seed | 1)
+  }
+
+  // Private Helpers
+
+  // Moves random hot pages to the back of the queue until enough are cooling
+  fn refill(&mut self) {
+    let target = (self.len() * COOLING_PERCENT / 100).max(1);
+    let mut attempts = self.0.len();
+
+    while self.cooling_len() < target && attempts > 0 {
+      attempts -= 1;
+
+      let idx = self.next_random() as usize % self.0.len();
+      let pid = self.0[idx];
+      if self.hint(pid) != AccessHint::WillNeed {
+        self.remove_hot(pid);
+        self.push_cooling(pid, false);
+      }
+    }
+  }
+
+  fn push_hot(&mut self, pid: usize) {
+    self.1.insert(pid, self.0.len());
+    self.0.push(pid);
+  }
+
+  fn remove_hot(&mut self, pid: usize) -> bool {
+    match self.1.remove(&pid) {
+      Some(idx) => {
+        self.0.swap_remove(idx);
+        if let Some(moved) = self.0.get(idx) {
+          self.1.insert(*moved, idx);
+        }
+
+        true
+      }
+
+      None => false
+    }
+  }
+
+  fn push_cooling(&mut self, pid: usize, front: bool) {
+    self.3.insert(pid);
+    if front { self.2.push_front(pid) } else { self.2.push_back(pid) }
+  }
+
+  fn remove_cooling(&mut self, pid: usize) -> bool {
+    if !self.3.remove(&pid) {
+      return false
+    }
+
+    if let Some(idx) = self.2.iter().position(|cooling| *cooling == pid) {
+      self.2.remove(idx);
+    }
+
+    true
+  }
+
+  // xorshift64, good enough to spread cooling picks
+  fn next_random(&mut self) -> u64 {
+    self.5 ^= self.5 << 13;
+    self.5 ^= self.5 >> 7;
+    self.5 ^= self.5 << 17;
+    self.5
+  }
+}
+
+impl EvictionPolicy for CoolingQueue {
+  fn len(&self) -> usize {
+    self.0.len() + self.2.len()
+  }
+
+  fn insert(&mut self, pid: usize, hint: AccessHint) {
+    if !self.is_cooling(pid) && !self.1.contains_key(&pid) {
+      self.push_hot(pid);
+    }
+
+    self.access(pid, hint);
+  }
+
+  fn access(&mut self, pid: usize, hint: AccessHint) {
+    if !self.is_cooling(pid) && !self.1.contains_key(&pid) {
+      return
+    }
+
+    match hint {
+      AccessHint::Normal => {
+        self.4.remove(&pid);
+        if self.remove_cooling(pid) {
+          self.push_hot(pid);
+        }
+      }
+
+      AccessHint::Sequential => {
+        self.4.insert(pid, hint);
+        if self.remove_hot(pid) {
+          self.push_cooling(pid, false);
+        }
+      }
+
+      AccessHint::WillNeed => {
+        self.4.insert(pid, hint);
+        if self.remove_cooling(pid) {
+          self.push_hot(pid);
+        }
+      }
+
+      AccessHint::DontNeed => {
+        self.4.insert(pid, hint);
+        if self.remove_hot(pid) || self.remove_cooling(pid) {
+          self.push_cooling(pid, true);
+        }
+      }
+    }
+  }
+
+  fn remove(&mut self, pid: usize) {
+    self.4.remove(&pid);
+    if !self.remove_hot(pid) {
+      self.remove_cooling(pid);
+    }
+  }
+
+  // The first cooling page the filter accepts, falling back to hot pages
+  fn victim(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<(usize, AccessHint)> {
+    self.refill();
+
+    let pid = self.2.iter().copied().find(|pid| accept(*pid))
+      .or_else(|| self.0.iter().copied().find(|pid| self.hint(*pid) != AccessHint::WillNeed && accept(*pid)))
+      .or_else(|| self.0.iter().copied().find(|pid| accept(*pid)))?;
+
+    let hint = self.hint(pid);
+    self.remove(pid);
+    Some((pid, hint))
+  }
+}
+
+impl Default for CoolingQueue {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_scan_does_not_cool_hot_pages() {
+    let mut queue = CoolingQueue::new();
+    queue.insert(1, AccessHint::WillNeed);
+    queue.insert(3, AccessHint::Normal);
+
+    for pid in (5..25).step_by(2) {
+      queue.insert(pid, AccessHint::Sequential);
+    }
+
+    // Scanned pages go first in the order they were read
+    assert_eq!(Some((5, AccessHint::Sequential)), queue.victim(&|_| true));
+    assert_eq!(Some((7, AccessHint::Sequential)), queue.victim(&|_| true));
+
+    // Touching a cooling page gives it a second chance
+    queue.access(9, AccessHint::Normal);
+    assert!(!queue.is_cooling(9));
+
+    queue.insert(27, AccessHint::DontNeed);
+    assert_eq!(Some((27, AccessHint::DontNeed)), queue.victim(&|_| true));
+
+    while let Some((pid, _)) = queue.victim(&|pid| pid > 3) {
+      assert!(pid > 3);
+    }
+
+    assert_eq!(Some((3, AccessHint::Normal)), queue.victim(&|_| true));
+    assert_eq!(Some((1, AccessHint::WillNeed)), queue.victim(&|_| true));
+    assert_eq!(0, queue.len());
+  }
+}
@@ -0,0 +1,199 @@
+use std::collections::{
+  BTreeSet, HashMap, VecDeque
+};
+
+use crate::{ AccessHint, EvictionPolicy };
+
+pub const DEFAULT_LRU_K: usize = 2;
+
+//
+// LRU-K (O'Neil et al.): the victim is the page whose K-th most recent access
+//  is the oldest, pages with fewer than K accesses go first, oldest first
+//
+// Scanned pages only ever count their first access so they keep going first,
+//  DontNeed forgets the history and WillNeed pages go last. Pages are kept
+//  ordered by rank so a victim is found without looking at every page
+//
+
+type Rank = ((usize, u64), usize);
+
+// K, access ticks per page with the most recent last, non-normal hints, the clock and pages by rank
+#[derive(Debug)]
+pub struct LruK(usize, HashMap<usize, VecDeque<u64>>, HashMap<usize, AccessHint>, u64, BTreeSet<Rank>);
+
+impl LruK {
+  pub fn k(&self) -> usize {
+    self.0
+  }
+
+  pub fn hint(&self, pid: usize) -> AccessHint {
+    self.2.get(&pid).copied().unwrap_or_default()
+  }
+
+  pub fn new(k: usize) -> Self {
+    Self(k.max(1), HashMap::new(), HashMap::new(), 0, BTreeSet::new())
+  }
+
+  // Private Helpers
+
+  fn record(&mut self, pid: usize) {
+    self.3 += 1;
+    let (k, tick) = (self.k(), self.3);
+
+    if let Some(history) = self.1.get_mut(&pid) {
+      history.push_back(tick);
+      while history.len() > k {
+        history.pop_front();
+      }
+    }
+  }
+
+  // Lower ranks are evicted first
+  fn rank(&self, pid: usize) -> Option<Rank> {
+    let history = self.1.get(&pid)?;
+    let oldest = history.front().copied().unwrap_or(0);
+
+    let rank = match self.hint(pid) {
+      AccessHint::DontNeed => (0, oldest),
+      AccessHint::Sequential => (1, oldest),
+      AccessHint::WillNeed => (4, oldest),
+      AccessHint::Normal if history.len() < self.k() => (2, oldest),
+      AccessHint::Normal => (3, oldest)
+    };
+
+    Some((rank, pid))
+  }
+
+  // Moves the page to its rank after the change
+  fn rerank<F: FnOnce(&mut Self)>(&mut self, pid: usize, change: F) {
+    if let Some(rank) = self.rank(pid) {
+      self.4.remove(&rank);
+    }
+
+    change(self);
+
+    if let Some(rank) = self.rank(pid) {
+      self.4.insert(rank);
+    }
+  }
+
+  // A page that was just inserted already has its access recorded
+  fn apply(&mut self, pid: usize, hint: AccessHint, record: bool) {
+    match hint {
+      AccessHint::Normal => {
+        self.2.remove(&pid);
+        if record {
+          self.record(pid);
+        }
+      }
+
+      AccessHint::Sequential => {
+        self.2.insert(pid, hint);
+      }
+
+      AccessHint::WillNeed => {
+        self.2.insert(pid, hint);
+        if record {
+          self.record(pid);
+        }
+      }
+
+      AccessHint::DontNeed => {
+        self.2.insert(pid, hint);
+        if let Some(history) = self.1.get_mut(&pid) {
+          history.clear();
+        }
+      }
+    }
+  }
+}
+
+impl EvictionPolicy for LruK {
+  fn len(&self) -> usize {
+    self.1.len()
+  }
+
+  fn insert(&mut self, pid: usize, hint: AccessHint) {
+    self.rerank(pid, |lru| {
+      let inserted = !lru.1.contains_key(&pid);
+      if inserted {
+        lru.1.insert(pid, VecDeque::with_capacity(lru.k()));
+        lru.record(pid);
+      }
+
+      lru.apply(pid, hint, !inserted);
+    });
+  }
+
+  fn access(&mut self, pid: usize, hint: AccessHint) {
+    if self.1.contains_key(&pid) {
+      self.rerank(pid, |lru| lru.apply(pid, hint, true));
+    }
+  }
+
+  fn remove(&mut self, pid: usize) {
+    self.rerank(pid, |lru| {
+      lru.1.remove(&pid);
+      lru.2.remove(&pid);
+    });
+  }
+
+  fn victim(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<(usize, AccessHint)> {
+    let pid = self.4.iter().map(|(_, pid)| *pid).find(|pid| accept(*pid))?;
+    let hint = self.hint(pid);
+
+    self.remove(pid);
+    Some((pid, hint))
+  }
+}
+
+impl Default for LruK {
+  fn default() -> Self {
+    Self::new(DEFAULT_LRU_K)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_victims_go_by_kth_most_recent_access() {
+    let mut lru = LruK::new(2);
+    lru.insert(1, AccessHint::Normal);
+    lru.insert(3, AccessHint::Normal);
+    lru.access(1, AccessHint::Normal);
+    lru.access(1, AccessHint::Normal);
+
+    // Page 3 was touched last but its second most recent access is older than page 1's
+    lru.access(3, AccessHint::Normal);
+
+    // Pages with fewer than K accesses go first
+    lru.insert(5, AccessHint::Normal);
+
+    let mut victims = vec![];
+    while let Some((pid, _)) = lru.victim(&|_| true) {
+      victims.push(pid);
+    }
+
+    assert_eq!(vec![5, 3, 1], victims);
+    assert_eq!(0, lru.len());
+  }
+
+  #[test]
+  fn test_hints_move_pages_between_ranks() {
+    let mut lru = LruK::new(2);
+    for pid in (1..7).step_by(2) {
+      lru.insert(pid, AccessHint::Normal);
+      lru.access(pid, AccessHint::Normal);
+    }
+
+    lru.access(5, AccessHint::DontNeed);
+    lru.access(1, AccessHint::WillNeed);
+
+    assert_eq!(Some((5, AccessHint::DontNeed)), lru.victim(&|_| true));
+    assert_eq!(Some((1, AccessHint::WillNeed)), lru.victim(&|pid| pid == 1));
+    assert_eq!(Some((3, AccessHint::Normal)), lru.victim(&|_| true));
+    assert_eq!(None, lru.victim(&|_| true));
+  }
+}
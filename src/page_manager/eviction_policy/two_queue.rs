@@ -0,0 +1,227 @@
+use std::collections::{
+  BTreeMap, HashMap, HashSet, VecDeque
+};
+
+use crate::{ AccessHint, EvictionPolicy };
+
+// Share of the tracked pages A1in holds before it gives up victims first
+pub const TWO_QUEUE_IN_PERCENT: usize = 25;
+
+// Share of the tracked pages A1out remembers after they were evicted
+pub const TWO_QUEUE_OUT_PERCENT: usize = 50;
+
+//
+// 2Q (Johnson & Shasha): new pages wait in the A1in FIFO, a page that comes
+//  back after being evicted from A1in is remembered by the A1out ghost queue
+//  and goes to the Am LRU where pages that proved they are hot live
+//
+// Scanned pages stay in A1in, are evicted before anything else and are never
+//  remembered by A1out so a scan can't promote them
+//
+
+// A1in, Am by access tick, Am ticks, A1out, A1out set, non-normal hints and the clock
+#[derive(Debug, Default)]
+pub struct TwoQueue(
+  VecDeque<usize>, BTreeMap<u64, usize>, HashMap<usize, u64>,
+  VecDeque<usize>, HashSet<usize>, HashMap<usize, AccessHint>, u64
+);
+
+impl TwoQueue {
+  pub fn hint(&self, pid: usize) -> AccessHint {
+    self.5.get(&pid).copied().unwrap_or_default()
+  }
+
+  pub fn is_hot(&self, pid: usize) -> bool {
+    self.2.contains_key(&pid)
+  }
+
+  pub fn is_remembered(&self, pid: usize) -> bool {
+    self.4.contains(&pid)
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Private Helpers
+
+  fn is_tracked(&self, pid: usize) -> bool {
+    self.is_hot(pid) || self.0.contains(&pid)
+  }
+
+  fn touch(&mut self, pid: usize) {
+    self.6 += 1;
+
+    if let Some(tick) = self.2.insert(pid, self.6) {
+      self.1.remove(&tick);
+    }
+
+    self.1.insert(self.6, pid);
+  }
+
+  fn remove_in(&mut self, pid: usize) -> bool {
+    match self.0.iter().position(|queued| *queued == pid) {
+      Some(idx) => self.0.remove(idx).is_some(),
+      None => false
+    }
+  }
+
+  fn remove_hot(&mut self, pid: usize) -> bool {
+    match self.2.remove(&pid) {
+      Some(tick) => self.1.remove(&tick).is_some(),
+      None => false
+    }
+  }
+
+  fn remember(&mut self, pid: usize) {
+    if self.4.insert(pid) {
+      self.3.push_back(pid);
+    }
+
+    let limit = (self.len() * TWO_QUEUE_OUT_PERCENT / 100).max(1);
+    while self.3.len() > limit {
+      if let Some(forgotten) = self.3.pop_front() {
+        self.4.remove(&forgotten);
+      }
+    }
+  }
+
+  fn find_in(&self, accept: &dyn Fn(usize) -> bool, hints: &[AccessHint]) -> Option<usize> {
+    self.0.iter().copied().find(|pid| hints.contains(&self.hint(*pid)) && accept(*pid))
+  }
+
+  fn find_hot(&self, accept: &dyn Fn(usize) -> bool) -> Option<usize> {
+    self.1.values().copied().find(|pid| self.hint(*pid) != AccessHint::WillNeed && accept(*pid))
+  }
+}
+
+impl EvictionPolicy for TwoQueue {
+  fn len(&self) -> usize {
+    self.0.len() + self.2.len()
+  }
+
+  fn insert(&mut self, pid: usize, hint: AccessHint) {
+    if self.is_tracked(pid) {
+      return self.access(pid, hint)
+    }
+
+    match hint {
+      AccessHint::Normal if self.is_remembered(pid) => self.touch(pid),
+      AccessHint::WillNeed => self.touch(pid),
+      AccessHint::DontNeed => self.0.push_front(pid),
+      _ => self.0.push_back(pid)
+    }
+
+    self.access(pid, hint);
+  }
+
+  fn access(&mut self, pid: usize, hint: AccessHint) {
+    if !self.is_tracked(pid) {
+      return
+    }
+
+    match hint {
+      AccessHint::Normal => {
+        self.5.remove(&pid);
+
+        // Touches while in A1in are correlated and don't promote the page
+        if self.is_hot(pid) {
+          self.touch(pid);
+        }
+      }
+
+      AccessHint::Sequential => {
+        self.5.insert(pid, hint);
+      }
+
+      AccessHint::WillNeed => {
+        self.5.insert(pid, hint);
+        if self.is_hot(pid) || self.remove_in(pid) {
+          self.touch(pid);
+        }
+      }
+
+      AccessHint::DontNeed => {
+        self.5.insert(pid, hint);
+        if self.remove_hot(pid) || self.remove_in(pid) {
+          self.0.push_front(pid);
+        }
+      }
+    }
+  }
+
+  fn remove(&mut self, pid: usize) {
+    self.5.remove(&pid);
+    if !self.remove_hot(pid) {
+      self.remove_in(pid);
+    }
+  }
+
+  fn victim(&mut self, accept: &dyn Fn(usize) -> bool) -> Option<(usize, AccessHint)> {
+    let limit = self.len() * TWO_QUEUE_IN_PERCENT / 100;
+    let normal = [AccessHint::Normal, AccessHint::Sequential, AccessHint::DontNeed];
+
+    let scanned = self.find_in(accept, &[AccessHint::DontNeed, AccessHint::Sequential]);
+    let (pid, remember) = match scanned {
+      Some(pid) => (pid, false),
+      None => {
+        let pid = if self.0.len() > limit { self.find_in(accept, &normal) } else { None }
+          .or_else(|| self.find_hot(accept))
+          .or_else(|| self.find_in(accept, &normal))
+          .or_else(|| self.0.iter().chain(self.1.values()).copied().find(|pid| accept(*pid)))?;
+
+        (pid, !self.is_hot(pid))
+      }
+    };
+
+    let hint = self.hint(pid);
+    self.remove(pid);
+    if remember {
+      self.remember(pid);
+    }
+
+    Some((pid, hint))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pages_evicted_from_a1in_are_promoted_when_they_come_back() {
+    let mut queue = TwoQueue::new();
+    for pid in (1..9).step_by(2) {
+      queue.insert(pid, AccessHint::Normal);
+    }
+
+    // Touches while in A1in don't promote
+    queue.access(1, AccessHint::Normal);
+    assert!(!queue.is_hot(1));
+
+    assert_eq!(Some((1, AccessHint::Normal)), queue.victim(&|_| true));
+    assert!(queue.is_remembered(1));
+
+    // A1out remembers it so it goes straight to Am and outlives the rest of A1in
+    queue.insert(1, AccessHint::Normal);
+    assert!(queue.is_hot(1));
+
+    let mut rest = vec![];
+    while let Some((pid, _)) = queue.victim(&|_| true) {
+      rest.push(pid);
+    }
+
+    assert_eq!(vec![3, 5, 7, 1], rest);
+  }
+
+  #[test]
+  fn test_untracked_pages_are_not_hinted() {
+    let mut queue = TwoQueue::new();
+    queue.insert(1, AccessHint::Normal);
+
+    queue.access(3, AccessHint::Sequential);
+    assert_eq!(AccessHint::Normal, queue.hint(3));
+    assert_eq!(Some((1, AccessHint::Normal)), queue.victim(&|_| true));
+    assert_eq!(None, queue.victim(&|_| true));
+  }
+}
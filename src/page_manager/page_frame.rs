@@ -0,0 +1,17 @@
+// Address and class of a frame holding a resident page
+#[derive(Debug)]
+pub struct PageFrame(usize, usize);
+
+impl PageFrame {
+  pub fn addr(&self) -> usize {
+    self.0
+  }
+
+  pub fn cid(&self) -> usize {
+    self.1
+  }
+
+  pub fn new(addr: usize, cid: usize) -> Self {
+    Self(addr, cid)
+  }
+}
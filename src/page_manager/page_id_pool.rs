@@ -1,27 +1,133 @@
+mod page_id_log;
+
+use anyhow::{ Result };
 use parking_lot::{ Mutex };
 
 use std::{
   collections::VecDeque,
+  path::Path,
   sync::atomic::{ AtomicUsize, Ordering }
 };
 
+pub use page_id_log::*;
+
+// Number of ids reserved with each durable log write, must be even to keep ids odd
+pub const PID_BLOCK_LEN: usize = 2usize.pow(16);
+
+// Number of free ids taken back with each durable log write
+pub const PID_REUSE_BATCH_LEN: usize = 64;
+
+//
+// Fresh ids come off of a counter with a fetch_add and only the thread that
+//  runs past the reserved block writes to the log. Freed ids are handed out
+//  again before fresh ones, a batch at a time: the whole batch is logged as
+//  reused with one sync and then claimed id by id. A crash leaks whatever
+//  was left of the claimed batch
+//
+
+// Next fresh id, reserved high-water mark, free and claimed ids, the log and how many free or claimed ids there are
 #[derive(Debug)]
-pub struct PageIdPool(AtomicUsize, Mutex<VecDeque<usize>>);
+pub struct PageIdPool(AtomicUsize, AtomicUsize, Mutex<(VecDeque<usize>, VecDeque<usize>)>, Mutex<Option<PageIdLog>>, AtomicUsize);
 
 impl PageIdPool {
-  pub fn next(&self) -> usize {
-    match self.free_ids().lock().pop_front() {
-      Some(free_id) => free_id,
-      None => self.generate_id()
+  pub fn next(&self) -> Result<usize> {
+    if self.available().load(Ordering::Acquire) == 0 {
+      return self.try_generate_id()
+    }
+
+    let mut free_ids = self.free_ids().lock();
+
+    if free_ids.1.is_empty() && !free_ids.0.is_empty() {
+      let len = free_ids.0.len().min(PID_REUSE_BATCH_LEN);
+      let batch: Vec<usize> = free_ids.0.drain(..len).collect();
+
+      if let Err(err) = self.try_log_all(&batch.iter().map(|pid| PageIdRecord::Reuse(*pid)).collect::<Vec<_>>()) {
+        for pid in batch.into_iter().rev() {
+          free_ids.0.push_front(pid);
+        }
+
+        return Err(err)
+      }
+
+      free_ids.1.extend(batch);
+    }
+
+    match free_ids.1.pop_front() {
+      Some(pid) => {
+        self.available().fetch_sub(1, Ordering::AcqRel);
+        Ok(pid)
+      }
+
+      None => {
+        drop(free_ids);
+        self.try_generate_id()
+      }
     }
   }
 
-  pub fn free(&mut self, pid: usize) {
-    self.free_ids().lock().push_back(pid)
+  pub fn free(&self, pid: usize) -> Result<()> {
+    let mut free_ids = self.free_ids().lock();
+    self.try_log_all(&[PageIdRecord::Free(pid)])?;
+    free_ids.0.push_back(pid);
+    self.available().fetch_add(1, Ordering::AcqRel);
+    Ok(())
+  }
+
+  //
+  // Compacts the log down to the current high-water mark and free list.
+  //  Claimed ids nobody took go back on the free list first, the rewritten
+  //  log has them as free so the next claim has to log them as reused again
+  //
+  pub fn try_checkpoint(&self) -> Result<()> {
+    let mut free_ids = self.free_ids().lock();
+    let mut log = self.log().lock();
+
+    match log.as_mut() {
+      None => Ok(()),
+      Some(log) => {
+        let claimed = std::mem::take(&mut free_ids.1);
+        for pid in claimed.into_iter().rev() {
+          free_ids.0.push_front(pid);
+        }
+
+        let hwm = self.reserved().load(Ordering::Acquire);
+        log.try_rewrite(&Self::checkpoint_records(hwm, free_ids.0.iter()))
+      }
+    }
   }
 
   pub fn new() -> Self {
-    Self(AtomicUsize::from(1), Mutex::new(VecDeque::new()))
+    Self(
+      AtomicUsize::from(1), AtomicUsize::from(usize::MAX), Mutex::new((VecDeque::new(), VecDeque::new())), Mutex::new(None),
+      AtomicUsize::new(0)
+    )
+  }
+
+  pub fn try_open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let (mut log, records) = PageIdLog::try_open(path)?;
+
+    let mut hwm = 1usize;
+    let mut free_ids = VecDeque::new();
+
+    for record in records {
+      match record {
+        PageIdRecord::Reserve(reserved) => hwm = hwm.max(reserved),
+        PageIdRecord::Free(pid) => free_ids.push_back(pid),
+        PageIdRecord::Reuse(pid) => {
+          if let Some(idx) = free_ids.iter().position(|free_id| *free_id == pid) {
+            free_ids.remove(idx);
+          }
+        }
+      }
+    }
+
+    log.try_rewrite(&Self::checkpoint_records(hwm, free_ids.iter()))?;
+
+    // Nothing below the high-water mark is safe to hand out again
+    Ok(Self(
+      AtomicUsize::from(hwm), AtomicUsize::from(hwm), Mutex::new((VecDeque::new(), VecDeque::new())), Mutex::new(Some(log)),
+      AtomicUsize::new(0)
+    ).with_free_ids(free_ids))
   }
 
   // Private Helpers
@@ -30,11 +136,167 @@ impl PageIdPool {
     &self.0
   }
 
-  fn free_ids(&self) -> &Mutex<VecDeque<usize>> {
+  fn reserved(&self) -> &AtomicUsize {
     &self.1
   }
 
-  fn generate_id(&self) -> usize {
-    self.counter().fetch_add(2, Ordering::SeqCst)
+  fn free_ids(&self) -> &Mutex<(VecDeque<usize>, VecDeque<usize>)> {
+    &self.2
+  }
+
+  fn log(&self) -> &Mutex<Option<PageIdLog>> {
+    &self.3
+  }
+
+  fn available(&self) -> &AtomicUsize {
+    &self.4
+  }
+
+  fn with_free_ids(self, free_ids: VecDeque<usize>) -> Self {
+    self.available().store(free_ids.len(), Ordering::Release);
+    self.free_ids().lock().0 = free_ids;
+    self
+  }
+
+  fn try_log_all(&self, records: &[PageIdRecord]) -> Result<()> {
+    match self.log().lock().as_mut() {
+      Some(log) => log.try_append_all(records),
+      None => Ok(())
+    }
+  }
+
+  fn try_generate_id(&self) -> Result<usize> {
+    let pid = self.counter().fetch_add(2, Ordering::SeqCst);
+
+    if pid < self.reserved().load(Ordering::Acquire) {
+      return Ok(pid)
+    }
+
+    self.try_reserve(pid)?;
+    Ok(pid)
+  }
+
+  // Only the thread that runs past the reserved block touches the log,
+  //  everyone else stays on the fetch_add above
+  fn try_reserve(&self, pid: usize) -> Result<()> {
+    let mut log = self.log().lock();
+
+    if pid < self.reserved().load(Ordering::Acquire) {
+      return Ok(())
+    }
+
+    let hwm = pid + PID_BLOCK_LEN;
+    if let Some(log) = log.as_mut() {
+      log.try_append_all(&[PageIdRecord::Reserve(hwm)])?;
+    }
+
+    self.reserved().store(hwm, Ordering::Release);
+    Ok(())
+  }
+
+  fn checkpoint_records<'a, I: Iterator<Item = &'a usize>>(hwm: usize, free_ids: I) -> Vec<PageIdRecord> {
+    let mut records = vec![PageIdRecord::Reserve(hwm)];
+    records.extend(free_ids.map(|pid| PageIdRecord::Free(*pid)));
+    records
+  }
+}
+
+impl Default for PageIdPool {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_try_open_survives_restart() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("vex-page-ids-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (first, second) = {
+      let pool = PageIdPool::try_open(&path)?;
+      let first = pool.next()?;
+      let second = pool.next()?;
+      pool.free(first)?;
+      (first, second)
+    };
+
+    let pool = PageIdPool::try_open(&path)?;
+
+    // The freed id comes back first and fresh ids start past the old reservation
+    assert_eq!(first, pool.next()?);
+    let fresh = pool.next()?;
+    assert!(fresh > second);
+    assert_eq!(1, fresh % 2);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_reuse_is_logged_a_batch_at_a_time() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("vex-page-ids-batch-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let pool = PageIdPool::try_open(&path)?;
+    let pids = (0..PID_REUSE_BATCH_LEN + 2).map(|_| pool.next()).collect::<Result<Vec<usize>>>()?;
+    for pid in &pids {
+      pool.free(*pid)?;
+    }
+
+    let log_len = || std::fs::metadata(&path).map(|metadata| metadata.len() as usize);
+    let before = log_len()?;
+    assert_eq!(pids[0], pool.next()?);
+    assert_eq!(before + PID_REUSE_BATCH_LEN * RECORD_LEN, log_len()?);
+
+    // The rest of the batch is handed out without touching the log
+    assert_eq!(pids[1], pool.next()?);
+    assert_eq!(before + PID_REUSE_BATCH_LEN * RECORD_LEN, log_len()?);
+
+    // Claimed ids nobody took are free again after a checkpoint
+    pool.try_checkpoint()?;
+    drop(pool);
+
+    let pool = PageIdPool::try_open(&path)?;
+    let reused = (0..pids.len() - 2).map(|_| pool.next()).collect::<Result<Vec<usize>>>()?;
+    assert_eq!(&pids[2..], &reused[..]);
+    assert!(pool.next()? > pids[pids.len() - 1]);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_ids_claimed_after_a_checkpoint_are_not_handed_out_twice() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("vex-page-ids-claim-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let pids = {
+      let pool = PageIdPool::try_open(&path)?;
+      let pids = (0..4).map(|_| pool.next()).collect::<Result<Vec<usize>>>()?;
+      for pid in &pids {
+        pool.free(*pid)?;
+      }
+
+      // The first claim logs the whole batch, the checkpoint puts the rest of it back
+      let mut taken = vec![pool.next()?];
+      pool.try_checkpoint()?;
+      taken.push(pool.next()?);
+      taken
+    };
+
+    // Reopening without another checkpoint is what a crash leaves behind
+    let pool = PageIdPool::try_open(&path)?;
+    let handed_out = (0..8).map(|_| pool.next()).collect::<Result<Vec<usize>>>()?;
+
+    for pid in &pids {
+      assert!(!handed_out.contains(pid), "Page id {} was handed out twice", pid);
+    }
+
+    std::fs::remove_file(&path)?;
+    Ok(())
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,137 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::{
+  fs::{ self, File, OpenOptions },
+  io::{ BufReader, Read, Write },
+  path::{ Path, PathBuf }
+};
+
+//
+// Every record is two big-endian words: a kind and a value
+//
+// Reserve(hwm) - every id below hwm may have been handed out
+// Free(pid)    - pid was released and may be handed out again
+// Reuse(pid)   - pid was taken back off of the free list
+//
+
+pub const RECORD_LEN: usize = 16;
+
+const RESERVE_KIND: u64 = 1;
+const FREE_KIND: u64 = 2;
+const REUSE_KIND: u64 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageIdRecord {
+  Reserve(usize),
+  Free(usize),
+  Reuse(usize)
+}
+
+impl PageIdRecord {
+  pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+    let (kind, value) = match self {
+      Self::Reserve(hwm) => (RESERVE_KIND, *hwm as u64),
+      Self::Free(pid) => (FREE_KIND, *pid as u64),
+      Self::Reuse(pid) => (REUSE_KIND, *pid as u64)
+    };
+
+    let mut bytes = [0u8; RECORD_LEN];
+    bytes[0..8].copy_from_slice(&kind.to_be_bytes());
+    bytes[8..16].copy_from_slice(&value.to_be_bytes());
+    bytes
+  }
+
+  pub fn try_from_bytes(bytes: &[u8; RECORD_LEN]) -> Result<Self> {
+    let kind = u64::from_be_bytes(bytes[0..8].try_into()?);
+    let value = u64::from_be_bytes(bytes[8..16].try_into()?) as usize;
+
+    match kind {
+      RESERVE_KIND => Ok(Self::Reserve(value)),
+      FREE_KIND => Ok(Self::Free(value)),
+      REUSE_KIND => Ok(Self::Reuse(value)),
+      _ => Err(anyhow!("Unknown page id record kind {}", kind))
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct PageIdLog(PathBuf, File);
+
+impl PageIdLog {
+  pub fn path(&self) -> &Path {
+    &self.0
+  }
+
+  fn file_mut(&mut self) -> &mut File {
+    &mut self.1
+  }
+
+  // Reserve and reuse records must be durable before the id is handed out,
+  //  a lost free record only leaks an id so it is left to the OS to flush
+  pub fn try_append_all(&mut self, records: &[PageIdRecord]) -> Result<()> {
+    let bytes: Vec<u8> = records.iter().flat_map(PageIdRecord::to_bytes).collect();
+    let file = self.file_mut();
+    file.write_all(&bytes)?;
+
+    if records.iter().any(|record| !matches!(record, PageIdRecord::Free(_))) {
+      file.sync_data()?;
+    }
+
+    Ok(())
+  }
+
+  // Replaces the log with the given records using write + rename
+  pub fn try_rewrite(&mut self, records: &[PageIdRecord]) -> Result<()> {
+    let tmp_path = self.path().with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      for record in records {
+        tmp.write_all(&record.to_bytes())?;
+      }
+      tmp.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, self.path())?;
+    self.1 = Self::try_open_append(self.path())?;
+
+    Ok(())
+  }
+
+  pub fn try_open<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<PageIdRecord>)> {
+    let path = path.as_ref().to_path_buf();
+    let records = Self::try_read_records(&path)?;
+    let file = Self::try_open_append(&path)?;
+    Ok((Self(path, file), records))
+  }
+
+  // Private Helpers
+
+  fn try_open_append(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+  }
+
+  fn try_read_records(path: &Path) -> Result<Vec<PageIdRecord>> {
+    let mut records = vec![];
+
+    if !path.exists() {
+      return Ok(records)
+    }
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut bytes = [0u8; RECORD_LEN];
+
+    loop {
+      match reader.read_exact(&mut bytes) {
+        Ok(_) => records.push(PageIdRecord::try_from_bytes(&bytes)?),
+        // A torn record at the tail is the only thing a crash can leave behind
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+        Err(err) => return Err(err.into())
+      }
+    }
+
+    Ok(records)
+  }
+}
@@ -0,0 +1,105 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::{ Condvar, Mutex };
+
+use std::collections::{
+  HashMap, HashSet
+};
+
+use crate::page_class;
+
+//
+// Pinned pages are never evicted, the limit on pinned bytes makes sure there
+//  is always something left to evict so pinning can't starve allocations
+//
+// Pinning and eviction take turns on the same lock, a page is either pinned
+//  before eviction looks at it or its eviction finishes before it is pinned
+//
+
+// Pin count and frame class by PID, PIDs being evicted and pinned bytes
+#[derive(Debug, Default)]
+struct PinState(HashMap<usize, (usize, usize)>, HashSet<usize>, usize);
+
+#[derive(Debug)]
+pub struct PagePins(Mutex<PinState>, Condvar, usize);
+
+impl PagePins {
+  pub fn limit(&self) -> usize {
+    self.2
+  }
+
+  pub fn pinned_bytes(&self) -> usize {
+    self.0.lock().2
+  }
+
+  pub fn count(&self, pid: usize) -> usize {
+    self.0.lock().0.get(&pid).map_or(0, |(count, _)| *count)
+  }
+
+  pub fn is_pinned(&self, pid: usize) -> bool {
+    self.count(pid) > 0
+  }
+
+  //
+  // Pins the page if it is still resident once any eviction of it finished
+  //  None means it was evicted in the meantime and has to be fetched again
+  //
+  pub fn try_pin(&self, pid: usize, resident: &dyn Fn() -> Option<(usize, usize)>) -> Result<Option<(usize, usize)>> {
+    let mut state = self.0.lock();
+
+    while state.1.contains(&pid) {
+      self.1.wait(&mut state);
+    }
+
+    let (address, cid) = match resident() {
+      Some(frame) => frame,
+      None => return Ok(None)
+    };
+
+    let len = page_class::size_of(cid);
+    let count = state.0.get(&pid).map_or(0, |(count, _)| *count);
+
+    if count == 0 && state.2 + len > self.limit() {
+      return Err(anyhow!("Pinning page {} would pin more than {} bytes", pid, self.limit()))
+    }
+
+    if count == 0 {
+      state.2 += len;
+    }
+
+    state.0.insert(pid, (count + 1, cid));
+    Ok(Some((address, cid)))
+  }
+
+  pub fn unpin(&self, pid: usize) {
+    let mut state = self.0.lock();
+
+    match state.0.get_mut(&pid) {
+      Some((count, _)) if *count > 1 => *count -= 1,
+      Some((_, cid)) => {
+        let len = page_class::size_of(*cid);
+        state.0.remove(&pid);
+        state.2 -= len;
+      }
+
+      None => {}
+    }
+  }
+
+  // False when the page is pinned and can't be evicted
+  pub fn begin_evict(&self, pid: usize) -> bool {
+    let mut state = self.0.lock();
+    !state.0.contains_key(&pid) && state.1.insert(pid)
+  }
+
+  pub fn end_evict(&self, pid: usize) {
+    self.0.lock().1.remove(&pid);
+    self.1.notify_all();
+  }
+
+  pub fn new(limit: usize) -> Self {
+    Self(Mutex::new(PinState::default()), Condvar::new(), limit)
+  }
+}
@@ -0,0 +1,106 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+use crate::{
+  PageLocation, PageStore, StorageTier, STORAGE_TIERS
+};
+
+// Placements are sharded by PID so allocations in different shards never share a lock
+pub const PLACEMENT_SHARDS: usize = 64;
+
+type Placements = RwLock<HashMap<usize, StorageTier>>;
+
+// One optional page store per storage tier plus the tier each page outside the default tier was placed in
+#[derive(Debug)]
+pub struct PageTiers(Vec<Option<PageStore>>, Vec<Placements>);
+
+impl PageTiers {
+  // PIDs are odd so the lowest bit says nothing about the shard
+  fn placements(&self, pid: usize) -> &Placements {
+    &self.1[(pid >> 1) % PLACEMENT_SHARDS]
+  }
+
+  pub fn store(&self, tier: StorageTier) -> Option<&PageStore> {
+    self.0[tier.index()].as_ref()
+  }
+
+  pub fn stores(&self) -> impl Iterator<Item = &PageStore> {
+    self.0.iter().flatten()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.stores().next().is_none()
+  }
+
+  pub fn tier(&self, pid: usize) -> StorageTier {
+    self.placements(pid).read().get(&pid).copied().unwrap_or_default()
+  }
+
+  // Pages of the default tier aren't recorded, which keeps most allocations on the read lock
+  pub fn place(&self, pid: usize, tier: StorageTier) {
+    let placements = self.placements(pid);
+
+    if tier != StorageTier::default() {
+      placements.write().insert(pid, tier);
+    } else if placements.read().contains_key(&pid) {
+      placements.write().remove(&pid);
+    }
+  }
+
+  // Falls back to the local tier and then to whichever tier is configured
+  pub fn try_resolve(&self, tier: StorageTier) -> Result<(StorageTier, &PageStore)> {
+    let fallbacks = [tier, StorageTier::Local].into_iter().chain(STORAGE_TIERS);
+
+    for tier in fallbacks {
+      if let Some(store) = self.store(tier) {
+        return Ok((tier, store))
+      }
+    }
+
+    Err(anyhow!("No page store configured for {:?} pages", tier))
+  }
+
+  pub fn try_store_for(&self, pid: usize) -> Result<&PageStore> {
+    Ok(self.try_resolve(self.tier(pid))?.1)
+  }
+
+  // Evicted pages are only known to the page table of the tier they were written to
+  pub fn try_locate(&self, pid: usize) -> Result<(&PageStore, PageLocation)> {
+    for tier in STORAGE_TIERS {
+      if let Some(store) = self.store(tier) {
+        if let Some(location) = store.location(pid) {
+          self.place(pid, tier);
+          return Ok((store, location))
+        }
+      }
+    }
+
+    Err(anyhow!("Page {} not found in any storage tier", pid))
+  }
+
+  pub fn remove(&self, pid: usize) {
+    self.placements(pid).write().remove(&pid);
+    for store in self.stores() {
+      store.remove(pid);
+    }
+  }
+
+  pub fn with(mut self, tier: StorageTier, store: PageStore) -> Self {
+    self.0[tier.index()] = Some(store);
+    self
+  }
+
+  pub fn new() -> Self {
+    Self(STORAGE_TIERS.iter().map(|_| None).collect(), (0..PLACEMENT_SHARDS).map(|_| RwLock::new(HashMap::new())).collect())
+  }
+}
+
+impl Default for PageTiers {
+  fn default() -> Self {
+    Self::new()
+  }
+}
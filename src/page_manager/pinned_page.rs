@@ -0,0 +1,34 @@
+use crate::{ Page, PageGuard, PagePins };
+
+// Keeps the page resident until it is dropped, see PageManager::try_pin
+#[derive(Debug)]
+pub struct PinnedPage<'a>(&'a PagePins, usize, usize, usize);
+
+impl<'a> PinnedPage<'a> {
+  pub fn pid(&self) -> usize {
+    self.1
+  }
+
+  pub fn addr(&self) -> usize {
+    self.2
+  }
+
+  pub fn cid(&self) -> usize {
+    self.3
+  }
+
+  // The frame can't move while the pin is held so every guard sees the same page
+  pub fn guard(&self) -> PageGuard<'_> {
+    PageGuard::new(Page::resident(self.addr(), self.cid()))
+  }
+
+  pub fn new(pins: &'a PagePins, pid: usize, addr: usize, cid: usize) -> Self {
+    Self(pins, pid, addr, cid)
+  }
+}
+
+impl<'a> Drop for PinnedPage<'a> {
+  fn drop(&mut self) {
+    self.0.unpin(self.pid());
+  }
+}
@@ -0,0 +1,188 @@
+use parking_lot::{ Condvar, Mutex };
+
+use std::{
+  collections::HashSet,
+  sync::{
+    Arc, Weak,
+    mpsc::{ self, Receiver, Sender }
+  },
+  thread
+};
+
+use crate::{ AccessHint, PageManager, Swip };
+
+pub const PREFETCH_THREADS: usize = 4;
+
+//
+// Tracks every page that is being faulted in, by a prefetch or a fetch, so a
+//  page is only ever read into one frame and everyone else waits for it
+//
+
+#[derive(Debug, Default)]
+pub struct Prefetcher(Mutex<HashSet<usize>>, Condvar, Mutex<Option<Sender<usize>>>);
+
+impl Prefetcher {
+  fn in_flight(&self) -> &Mutex<HashSet<usize>> {
+    &self.0
+  }
+
+  pub fn in_flight_len(&self) -> usize {
+    self.in_flight().lock().len()
+  }
+
+  // Queues every unswizzled swip that isn't already resident or on its way
+  pub fn prefetch(&self, pages: &Arc<PageManager>, swips: &[Swip]) -> usize {
+    let mut queued = 0;
+    let mut sender = self.2.lock();
+    let sender = sender.get_or_insert_with(|| Self::spawn(Arc::downgrade(pages)));
+
+    let mut in_flight = self.in_flight().lock();
+    for pid in swips.iter().filter_map(Swip::pid) {
+      if in_flight.contains(&pid) || pages.is_resident(pid) {
+        continue
+      }
+
+      if sender.send(pid).is_ok() {
+        in_flight.insert(pid);
+        queued += 1;
+      }
+    }
+
+    queued
+  }
+
+  // Waits out anyone else's fault-in of the page and claims it, the caller checks whether it is resident by now
+  pub fn begin(&self, pid: usize) {
+    let mut in_flight = self.in_flight().lock();
+
+    while in_flight.contains(&pid) {
+      self.1.wait(&mut in_flight);
+    }
+
+    in_flight.insert(pid);
+  }
+
+  pub fn complete(&self, pid: usize) {
+    self.in_flight().lock().remove(&pid);
+    self.1.notify_all();
+  }
+
+  fn spawn(pages: Weak<PageManager>) -> Sender<usize> {
+    let (sender, receiver) = mpsc::channel::<usize>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..PREFETCH_THREADS {
+      let pages = pages.clone();
+      let receiver = receiver.clone();
+      thread::spawn(move || Self::run(pages, receiver));
+    }
+
+    sender
+  }
+
+  // Exits once the page manager is gone
+  fn run(pages: Weak<PageManager>, receiver: Arc<Mutex<Receiver<usize>>>) {
+    loop {
+      let pid = match receiver.lock().recv() {
+        Ok(pid) => pid,
+        Err(_) => return
+      };
+
+      let pages = match pages.upgrade() {
+        Some(pages) => pages,
+        None => return
+      };
+
+      // A failed prefetch is retried by the next fetch of the page
+      let _ = pages.try_fault_in(pid, AccessHint::Normal);
+      pages.prefetcher().complete(pid);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ PageIdPool, PageStore, PageTiers, ReadAhead, StorageTier };
+  use anyhow::Result;
+
+  use std::{
+    sync::atomic::{ AtomicBool, Ordering },
+    time::Duration
+  };
+
+  #[test]
+  fn test_prefetch_faults_in_unswizzled_pages_once() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-prefetch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = Arc::new(PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?);
+
+    let mut swips = vec![];
+    for _ in 0..3 {
+      let mut page = pages.try_alloc(64)?;
+      swips.push(Swip::from(page.try_write()?.swip().value()));
+      pages.try_evict(page)?;
+    }
+
+    // Resident and swizzled swips are skipped, as is a page already on its way
+    let resident = pages.try_alloc(64)?;
+    let resident = Swip::unswizzled(resident.pid(), 12);
+    let queued = pages.prefetch(&[swips[0], swips[1], swips[0], resident, Swip::swizzled(4096)]);
+    assert_eq!(2, queued);
+
+    for swip in &swips[0..2] {
+      pages.try_fetch(swip.pid().unwrap_or_default())?;
+      assert!(pages.is_resident(swip.pid().unwrap_or_default()));
+    }
+
+    // A scan that keeps landing on the page it was told comes next widens the window
+    let mut read_ahead = ReadAhead::default();
+    assert_eq!(1, read_ahead.visit(&pages, swips[0], &swips[1..]));
+    assert_eq!(2, read_ahead.window());
+    read_ahead.visit(&pages, swips[1], &swips[2..]);
+    assert_eq!(4, read_ahead.window());
+    pages.try_fetch(swips[2].pid().unwrap_or_default())?;
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_fetch_waits_for_the_fault_in_in_flight() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-prefetch-wait-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(&dir)?);
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?;
+    let page = pages.try_alloc(64)?;
+    let pid = page.pid();
+    pages.try_evict(page)?;
+
+    // The fetch finds the page another fault-in read while it waited instead of reading it again
+    pages.prefetcher().begin(pid);
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| -> Result<()> {
+      let fetch = scope.spawn(|| -> Result<usize> {
+        let page = pages.try_fetch(pid)?;
+        done.store(true, Ordering::SeqCst);
+        Ok(page.page().addr())
+      });
+
+      thread::sleep(Duration::from_millis(50));
+      assert!(!done.load(Ordering::SeqCst));
+
+      let frame = pages.try_fault_in(pid, AccessHint::Normal)?;
+      pages.prefetcher().complete(pid);
+
+      assert_eq!(frame.addr(), fetch.join().map_err(|_| anyhow::anyhow!("Fetch panicked"))??);
+      Ok(())
+    })?;
+
+    assert_eq!(0, pages.prefetcher().in_flight_len());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
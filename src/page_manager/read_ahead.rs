@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use crate::{ PageManager, Swip };
+
+pub const MIN_READ_AHEAD: usize = 2;
+pub const MAX_READ_AHEAD: usize = 64;
+
+//
+// Sequential read-ahead for scans that walk a chain of pages, like the leaves
+//  of a B+Tree. While every visit lands on the page the previous visit said
+//  comes next the window doubles, any jump shrinks it back to the minimum.
+//
+#[derive(Debug)]
+pub struct ReadAhead(usize, usize, Option<Swip>);
+
+impl ReadAhead {
+  pub fn window(&self) -> usize {
+    self.0
+  }
+
+  // Takes the swip being visited and the swips that follow it in the chain,
+  //  returns how many page fault-ins were queued
+  pub fn visit(&mut self, pages: &Arc<PageManager>, swip: Swip, following: &[Swip]) -> usize {
+    self.0 = if self.2 == Some(swip) {
+      (self.0 * 2).min(self.1)
+    } else {
+      MIN_READ_AHEAD
+    };
+
+    self.2 = following.first().copied();
+    pages.prefetch(&following[..self.window().min(following.len())])
+  }
+
+  pub fn new(max_window: usize) -> Self {
+    Self(MIN_READ_AHEAD, max_window.max(MIN_READ_AHEAD), None)
+  }
+}
+
+impl Default for ReadAhead {
+  fn default() -> Self {
+    Self::new(MAX_READ_AHEAD)
+  }
+}
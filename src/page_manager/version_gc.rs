@@ -0,0 +1,79 @@
+use parking_lot::Mutex;
+
+use std::{
+  collections::{ BTreeMap, HashSet, VecDeque },
+  sync::atomic::{ AtomicUsize, Ordering }
+};
+
+//
+// Past page versions are kept until no snapshot can see them anymore (see
+//  docs/design.md). Every snapshot that is being read from is registered
+//  here and the oldest one is the horizon, a version chain is cut below the
+//  newest version committed at or before the horizon since every reader
+//  sees that version or a newer one
+//
+// Chains are collected a few heads at a time so collection never holds up
+//  the workers for long, heads that still have past versions are queued again
+//
+
+// Registered snapshots and how often, heads with past versions, queued heads and the latest commit time
+#[derive(Debug, Default)]
+pub struct VersionGc(Mutex<BTreeMap<usize, usize>>, Mutex<(VecDeque<usize>, HashSet<usize>)>, AtomicUsize);
+
+impl VersionGc {
+  pub fn latest(&self) -> usize {
+    self.2.load(Ordering::Acquire)
+  }
+
+  // The oldest registered snapshot or the latest commit time when nobody is reading
+  pub fn horizon(&self) -> usize {
+    self.0.lock().keys().next().copied().unwrap_or_else(|| self.latest())
+  }
+
+  pub fn register(&self, snapshot: usize) {
+    *self.0.lock().entry(snapshot).or_insert(0) += 1;
+  }
+
+  pub fn release(&self, snapshot: usize) {
+    let mut snapshots = self.0.lock();
+
+    if let Some(count) = snapshots.get_mut(&snapshot) {
+      *count -= 1;
+      if *count == 0 {
+        snapshots.remove(&snapshot);
+      }
+    }
+  }
+
+  pub fn advance(&self, time: usize) {
+    self.2.fetch_max(time, Ordering::AcqRel);
+  }
+
+  pub fn pending(&self) -> usize {
+    self.1.lock().0.len()
+  }
+
+  pub fn track(&self, head: usize) {
+    let mut heads = self.1.lock();
+
+    if heads.1.insert(head) {
+      heads.0.push_back(head);
+    }
+  }
+
+  pub fn next_batch(&self, len: usize) -> Vec<usize> {
+    let mut heads = self.1.lock();
+    let len = len.min(heads.0.len());
+    let batch: Vec<usize> = heads.0.drain(..len).collect();
+
+    for head in &batch {
+      heads.1.remove(head);
+    }
+
+    batch
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
@@ -0,0 +1,645 @@
+mod page_cipher;
+mod page_codec;
+mod page_fence;
+mod page_location;
+mod page_table;
+mod slot_header;
+mod slot_pool;
+mod storage_tier;
+mod write_sequence;
+
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::{ Mutex, RwLock };
+
+use std::{
+  borrow::Cow,
+  collections::{ HashMap, HashSet },
+  fs::{ self, File, OpenOptions },
+  io::{ Cursor, Write },
+  os::unix::fs::FileExt,
+  path::{ Path, PathBuf },
+  sync::{
+    Arc,
+    atomic::{ AtomicU32, Ordering }
+  }
+};
+
+use crate::{
+  HEADER_LEN, MAX_CLASS_ID, MIN_CLASS_ID,
+  page_class, PageManager
+};
+
+pub use page_cipher::*;
+pub use page_codec::*;
+pub use page_fence::*;
+pub use page_location::*;
+pub use page_table::*;
+pub use slot_header::*;
+pub use slot_pool::*;
+pub use storage_tier::*;
+pub use write_sequence::*;
+
+pub const TABLE_FILE: &str = "page_table";
+pub const SEQUENCE_FILE: &str = "sequence";
+
+// The page table is stored in pages of this class, each holds an entry count and the entries
+pub const TABLE_PAGE_LEN: usize = 2usize.pow(16);
+pub const TABLE_PAGE_ENTRIES: usize = (TABLE_PAGE_LEN - HEADER_LEN - 8) / LOCATION_LEN;
+
+//
+// Every slot records the fencing epoch it was written at. The store keeps the
+//  highest epoch it has seen in a slot and refuses writes below it, so once a
+//  newer owner's page has been read nothing older is written over the store
+//
+// <store>/
+//   page_table                   root of the page table, an entry count and the location of every table page
+//   sequence                     store ID and write sequence high-water mark for page nonces
+//   class-<cid>/extent-<n>       slots of the class
+//
+
+type ExtentFiles = HashMap<(usize, usize), Arc<File>>;
+
+#[derive(Debug)]
+pub struct PageStore {
+  dir: PathBuf,
+  table: RwLock<PageTable>,
+  extents: Mutex<ExtentFiles>,
+  // Extents written to since the last checkpoint synced them
+  unsynced: Mutex<HashSet<(usize, usize)>>,
+  fence: Option<Arc<dyn PageFence>>,
+  codecs: Vec<PageCodec>,
+  cipher: Option<Arc<dyn PageCipher>>,
+  sequence: WriteSequence,
+  epoch: AtomicU32
+}
+
+impl PageStore {
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  fn table(&self) -> &RwLock<PageTable> {
+    &self.table
+  }
+
+  fn extents(&self) -> &Mutex<ExtentFiles> {
+    &self.extents
+  }
+
+  pub fn codec(&self, cid: usize) -> PageCodec {
+    self.codecs[page_class::index_of(cid)]
+  }
+
+  fn cipher(&self) -> Option<&dyn PageCipher> {
+    self.cipher.as_deref()
+  }
+
+  fn sequence(&self) -> &WriteSequence {
+    &self.sequence
+  }
+
+  // Highest epoch written to or read from a slot of this store
+  pub fn epoch(&self) -> u32 {
+    self.epoch.load(Ordering::Acquire)
+  }
+
+  // Epoch a write is made at, 0 for stores without a fence
+  fn try_fence(&self) -> Result<u32> {
+    let epoch = match &self.fence {
+      Some(fence) => fence.try_check()?,
+      None => return Ok(0)
+    };
+
+    let epoch = u32::try_from(epoch).map_err(|_| anyhow!("Fencing epoch {} does not fit in a slot header", epoch))?;
+    let seen = self.epoch.fetch_max(epoch, Ordering::AcqRel);
+
+    if epoch < seen {
+      return Err(anyhow!("Write at epoch {} is fenced off by a page written at epoch {}", epoch, seen))
+    }
+
+    Ok(epoch)
+  }
+
+  pub fn location(&self, pid: usize) -> Option<PageLocation> {
+    self.table().read().get(pid)
+  }
+
+  // PIDs of the pages the page table was stored in at the last checkpoint
+  pub fn table_pages(&self) -> Vec<usize> {
+    self.table().read().root().iter().map(|(pid, _)| *pid).collect()
+  }
+
+  // Writes a whole page frame into a new slot and publishes it once the write is done, readers see the old or the new page
+  pub fn try_write(&self, pid: usize, frame: &[u8]) -> Result<PageLocation> {
+    let epoch = self.try_fence()?;
+    let cid = Self::try_class_of(frame)?;
+    let codec = self.codec(cid);
+
+    let compressed = codec.try_compress(frame)?;
+    let location = self.table().write().reserve(cid);
+
+    match self.try_write_at(pid, location, epoch, frame, compressed) {
+      Ok(location) => {
+        self.table().write().publish(pid, location);
+        Ok(location)
+      }
+
+      Err(err) => {
+        self.table().write().release(location);
+        Err(err)
+      }
+    }
+  }
+
+  // Reads hold the table so a slot isn't released and reused underneath them
+  pub fn try_read(&self, pid: usize, frame: &mut [u8]) -> Result<PageLocation> {
+    let table = self.table().read();
+
+    match table.get(pid) {
+      Some(location) => self.try_read_at(pid, location, frame),
+      None => Err(anyhow!("Page {} not found in page table", pid))
+    }
+  }
+
+  pub fn try_read_at(&self, pid: usize, location: PageLocation, frame: &mut [u8]) -> Result<PageLocation> {
+    if frame.len() != location.len() {
+      return Err(anyhow!("Page {} is {} bytes but frame is {} bytes", pid, location.len(), frame.len()))
+    }
+
+    let extent = self.try_extent(location)?;
+    let mut header = [0u8; SLOT_HEADER_LEN];
+    extent.read_exact_at(&mut header, location.offset() as u64)?;
+    let header = SlotHeader::try_from_bytes(&header)?;
+    self.epoch.fetch_max(header.epoch(), Ordering::AcqRel);
+
+    if !header.is_sealed() && !location.is_compressed() {
+      extent.read_exact_at(frame, location.data_offset() as u64)?;
+      return Ok(location)
+    }
+
+    let mut data = vec![0u8; location.stored_len()];
+    extent.read_exact_at(&mut data, location.data_offset() as u64)?;
+
+    if header.is_sealed() {
+      self.try_unseal(pid, location, header, &mut data)?;
+    }
+
+    location.codec().try_decompress(&data, frame)?;
+    Ok(location)
+  }
+
+  pub fn remove(&self, pid: usize) -> Option<PageLocation> {
+    self.table().write().remove(pid)
+  }
+
+  //
+  // Stores the page table in pages of the tier this store backs and makes
+  //  them the new root with an atomic rename, the pages of the previous
+  //  table are freed once the new root is durable. Placements made since
+  //  the last checkpoint are lost on a crash until we have a log to replay
+  //  them from (see docs/acid/checkpoints.md). Every extent written since
+  //  the last checkpoint is synced before the rename so the root never
+  //  points at slots that aren't on disk, and slots pages moved out of
+  //  before the table was copied are only released once it is durable
+  //
+  pub fn try_checkpoint(&self, pages: &PageManager, tier: StorageTier) -> Result<()> {
+    self.try_fence()?;
+
+    let (previous, entries, retired) = {
+      let mut table = self.table().write();
+      let previous: HashSet<usize> = table.root().iter().map(|(pid, _)| *pid).collect();
+      let entries: Vec<(usize, PageLocation)> = table.entries().into_iter().filter(|(pid, _)| !previous.contains(pid)).collect();
+      (previous, entries, table.take_retired())
+    };
+
+    let mut root = vec![];
+    let mut written = Ok(());
+
+    for chunk in entries.chunks(TABLE_PAGE_ENTRIES) {
+      match self.try_write_table_page(pages, tier, chunk) {
+        Ok(entry) => root.push(entry),
+        Err(err) => {
+          written = Err(err);
+          break
+        }
+      }
+    }
+
+    if let Err(err) = written.and_then(|_| self.try_sync_extents()).and_then(|_| self.try_write_root(&root)) {
+      self.table().write().restore_retired(retired);
+      for (pid, _) in root {
+        pages.try_free_stored(pid)?;
+      }
+
+      return Err(err)
+    }
+
+    {
+      let mut table = self.table().write();
+      table.set_root(root);
+      table.release_all(retired);
+    }
+
+    for pid in previous {
+      pages.try_free_stored(pid)?;
+    }
+
+    Ok(())
+  }
+
+  // Pages of this class and every larger class are compressed with the codec
+  pub fn with_codec_from(mut self, min_cid: usize, codec: PageCodec) -> Self {
+    for cid in min_cid.max(MIN_CLASS_ID)..=MAX_CLASS_ID {
+      self.codecs[page_class::index_of(cid)] = codec;
+    }
+
+    self
+  }
+
+  pub fn with_codec(mut self, cid: usize, codec: PageCodec) -> Self {
+    self.codecs[page_class::index_of(cid)] = codec;
+    self
+  }
+
+  pub fn with_fence(mut self, fence: Arc<dyn PageFence>) -> Self {
+    self.fence = Some(fence);
+    self
+  }
+
+  pub fn try_open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+    Self::try_open_with(dir, None)
+  }
+
+  // Pages are sealed with the cipher, which has to be there from the start to read the page table back
+  pub fn try_open_with<P: AsRef<Path>>(dir: P, cipher: Option<Arc<dyn PageCipher>>) -> Result<Self> {
+    let dir = dir.as_ref().to_path_buf();
+    fs::create_dir_all(&dir)?;
+
+    let sequence = WriteSequence::try_open(dir.join(SEQUENCE_FILE))?;
+    let codecs = (MIN_CLASS_ID..=MAX_CLASS_ID).map(|_| PageCodec::None).collect();
+    let store = Self {
+      dir,
+      table: RwLock::new(PageTable::new()),
+      extents: Mutex::new(HashMap::new()),
+      unsynced: Mutex::new(HashSet::new()),
+      fence: None,
+      codecs,
+      cipher,
+      sequence,
+      epoch: AtomicU32::new(0)
+    };
+
+    let table = store.try_read_table()?;
+    *store.table().write() = table;
+    Ok(store)
+  }
+
+  // Private Helpers
+
+  fn class_dir(&self, cid: usize) -> PathBuf {
+    self.dir().join(format!("class-{}", cid))
+  }
+
+  fn extent_path(&self, cid: usize, extent: usize) -> PathBuf {
+    self.class_dir(cid).join(format!("extent-{}", extent))
+  }
+
+  fn try_extent(&self, location: PageLocation) -> Result<Arc<File>> {
+    self.try_extent_file((location.cid(), location.extent()))
+  }
+
+  fn try_extent_file(&self, key: (usize, usize)) -> Result<Arc<File>> {
+    let mut extents = self.extents().lock();
+
+    if let Some(file) = extents.get(&key) {
+      return Ok(file.clone())
+    }
+
+    let path = self.extent_path(key.0, key.1);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    let file = Arc::new(OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?);
+    extents.insert(key, file.clone());
+
+    Ok(file)
+  }
+
+  // Compresses and seals the frame into the reserved slot, returns the location with how it was encoded
+  fn try_write_at(&self, pid: usize, location: PageLocation, epoch: u32, frame: &[u8], compressed: Option<Vec<u8>>) -> Result<PageLocation> {
+    let (location, data) = match compressed {
+      Some(compressed) => (location.with_encoding(self.codec(location.cid()), compressed.len()), Cow::Owned(compressed)),
+      None => (location.with_encoding(PageCodec::None, frame.len()), Cow::Borrowed(frame))
+    };
+
+    let (header, data) = match self.cipher() {
+      Some(cipher) => {
+        let mut sealed = data.into_owned();
+        let header = self.try_seal(cipher, pid, location, &mut sealed)?;
+        (header, Cow::Owned(sealed))
+      }
+
+      None => (SlotHeader::plain(), data)
+    };
+
+    let extent = self.try_extent(location)?;
+    self.unsynced.lock().insert((location.cid(), location.extent()));
+    extent.write_all_at(&header.with_epoch(epoch).to_bytes(), location.offset() as u64)?;
+    extent.write_all_at(&data, location.data_offset() as u64)?;
+
+    Ok(location)
+  }
+
+  // Syncs the data of every extent written since the last checkpoint and the directories of their classes
+  fn try_sync_extents(&self) -> Result<()> {
+    let unsynced: Vec<(usize, usize)> = self.unsynced.lock().drain().collect();
+
+    let synced = unsynced.iter().try_for_each(|key| Ok(self.try_extent_file(*key)?.sync_data()?)).and_then(|_| {
+      let classes: HashSet<usize> = unsynced.iter().map(|(cid, _)| *cid).collect();
+      classes.into_iter().try_for_each(|cid| Ok(File::open(self.class_dir(cid))?.sync_all()?))
+    });
+
+    if synced.is_err() {
+      self.unsynced.lock().extend(unsynced);
+    }
+
+    synced
+  }
+
+  fn try_seal(&self, cipher: &dyn PageCipher, pid: usize, location: PageLocation, data: &mut [u8]) -> Result<SlotHeader> {
+    let key_id = cipher.key_id();
+    let lsn = self.sequence().try_next()?;
+    let tag = cipher.try_seal(key_id, &page_nonce(self.sequence().store_id(), pid, lsn), &Self::page_aad(pid, location), data)?;
+    Ok(SlotHeader::sealed(key_id, lsn, tag))
+  }
+
+  fn try_unseal(&self, pid: usize, location: PageLocation, header: SlotHeader, data: &mut [u8]) -> Result<()> {
+    match self.cipher() {
+      Some(cipher) => cipher.try_open(header.key_id(), &page_nonce(self.sequence().store_id(), pid, header.lsn()), &Self::page_aad(pid, location), data, header.tag()),
+      None => Err(anyhow!("Page {} is sealed with key {} but no cipher is configured", pid, header.key_id()))
+    }
+  }
+
+  // Binds the sealed bytes to the page and slot they were written for
+  fn page_aad(pid: usize, location: PageLocation) -> [u8; LOCATION_LEN] {
+    location.to_bytes(pid)
+  }
+
+  fn try_class_of(frame: &[u8]) -> Result<usize> {
+    let cid = frame.len().trailing_zeros() as usize;
+
+    if page_class::size_of(cid) == frame.len() && (MIN_CLASS_ID..=MAX_CLASS_ID).contains(&cid) {
+      Ok(cid)
+    } else {
+      Err(anyhow!("Frame of {} bytes does not match any page class", frame.len()))
+    }
+  }
+
+  // Writes the entries into a new page of the tier and evicts it, returns where it was stored
+  fn try_write_table_page(&self, pages: &PageManager, tier: StorageTier, entries: &[(usize, PageLocation)]) -> Result<(usize, PageLocation)> {
+    let mut data = Vec::with_capacity(8 + entries.len() * LOCATION_LEN);
+    data.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+
+    for (pid, location) in entries {
+      data.extend_from_slice(&location.to_bytes(*pid));
+    }
+
+    let mut page = pages.try_alloc_in(TABLE_PAGE_LEN as u32 - HEADER_LEN as u32, tier)?;
+    let pid = page.pid();
+
+    let written = page.try_write().and_then(|mut latched| latched.write(0, data.len(), &mut Cursor::new(&data)));
+    if let Err(err) = written {
+      pages.try_free(page)?;
+      return Err(err)
+    }
+
+    pages.try_evict(page)?;
+
+    match self.location(pid) {
+      Some(location) => Ok((pid, location)),
+      None => Err(anyhow!("Page table page {} was not written to the store of {:?} pages", pid, tier))
+    }
+  }
+
+  // Write + fsync a sibling file then rename it over the root
+  fn try_write_root(&self, root: &[(usize, PageLocation)]) -> Result<()> {
+    let path = self.dir().join(TABLE_FILE);
+    let tmp_path = path.with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      tmp.write_all(&(root.len() as u64).to_be_bytes())?;
+      for (pid, location) in root {
+        tmp.write_all(&location.to_bytes(*pid))?;
+      }
+      tmp.sync_all()?;
+    }
+
+    self.try_fence()?;
+    fs::rename(&tmp_path, &path)?;
+    File::open(self.dir())?.sync_all()?;
+
+    Ok(())
+  }
+
+  fn try_read_table(&self) -> Result<PageTable> {
+    let path = self.dir().join(TABLE_FILE);
+    if !path.exists() {
+      return Ok(PageTable::new())
+    }
+
+    let bytes = fs::read(&path)?;
+    let count = u64::from_be_bytes(bytes.get(0..8).ok_or_else(|| anyhow!("Page table root {} is truncated", path.display()))?.try_into()?) as usize;
+    let root = bytes[8..].chunks(LOCATION_LEN).take(count).map(PageLocation::try_from_bytes).collect::<Result<Vec<_>>>()?;
+
+    if root.len() != count {
+      return Err(anyhow!("Page table root {} is truncated", path.display()))
+    }
+
+    let mut entries = vec![];
+    for (pid, location) in root.iter() {
+      let mut frame = vec![0u8; location.len()];
+      self.try_read_at(*pid, *location, &mut frame)?;
+
+      let data = &frame[HEADER_LEN..];
+      let count = u64::from_be_bytes(data[0..8].try_into()?) as usize;
+
+      for entry in data[8..].chunks(LOCATION_LEN).take(count) {
+        entries.push(PageLocation::try_from_bytes(entry)?);
+      }
+    }
+
+    Ok(PageTable::from_entries(entries, root))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ PageIdPool, PageTiers };
+
+  #[test]
+  fn test_try_checkpoint_relocates_and_reopens() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-store-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let small = vec![7u8; page_class::size_of(12)];
+    let large = vec![9u8; page_class::size_of(13)];
+
+    let first_table = {
+      let store = PageStore::try_open(&dir)?;
+      store.try_write(1003, &small)?;
+      store.try_write(1005, &small)?;
+
+      // Growing page 1003 moves it into the next class and frees its old slot
+      let moved = store.try_write(1003, &large)?;
+      assert_eq!(13, moved.cid());
+      assert_eq!(0, store.try_write(1007, &small)?.slot());
+
+      let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), PageTiers::new().with(StorageTier::Local, store))?;
+      pages.try_checkpoint()?;
+      let store = pages.store(StorageTier::Local).ok_or_else(|| anyhow!("No local store"))?;
+      let first_table = store.table_pages();
+
+      // The checkpointed slot of a rewritten page is kept until the next checkpoint is durable
+      assert_eq!(2, store.try_write(1005, &small)?.slot());
+      assert_eq!(3, store.try_write(1009, &small)?.slot());
+
+      // The next checkpoint stores the table in new pages and frees the old ones
+      pages.try_checkpoint()?;
+      assert_eq!(1, pages.store(StorageTier::Local).ok_or_else(|| anyhow!("No local store"))?.try_write(1011, &small)?.slot());
+      first_table
+    };
+
+    let store = PageStore::try_open(&dir)?;
+    let mut frame = vec![0u8; page_class::size_of(13)];
+    store.try_read(1003, &mut frame)?;
+
+    assert_eq!(large, frame);
+    assert_eq!(Some(PageLocation::from_slot(12, 2)), store.location(1005));
+    assert_eq!(None, store.location(1011));
+    assert_eq!(1, first_table.len());
+    assert_eq!(1, store.table_pages().len());
+    assert_ne!(first_table, store.table_pages());
+    assert_eq!(None, store.location(first_table[0]));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_slots_are_kept_until_the_root_is_durable() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-store-durable-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let frame = vec![5u8; page_class::size_of(12)];
+    let store = PageStore::try_open(&dir)?;
+    assert_eq!(0, store.try_write(3, &frame)?.slot());
+
+    let pages = PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), PageTiers::new().with(StorageTier::Local, store))?;
+    let store = || pages.store(StorageTier::Local).ok_or_else(|| anyhow!("No local store"));
+    pages.try_checkpoint()?;
+    assert!(store()?.unsynced.lock().is_empty());
+
+    // Page 3 moves out of slot 0, which the durable root still points at
+    assert_eq!(1, store()?.try_write(3, &frame)?.slot());
+
+    // A checkpoint that can't write its root leaves slot 0 alone
+    fs::create_dir_all(dir.join(TABLE_FILE).with_extension("tmp"))?;
+    assert!(pages.try_checkpoint().is_err());
+    assert_eq!(2, store()?.try_write(5, &frame)?.slot());
+
+    let mut read = vec![0u8; frame.len()];
+    store()?.try_read_at(3, PageLocation::from_slot(12, 0), &mut read)?;
+    assert_eq!(frame, read);
+
+    // Once a root without it is durable the slot is reused
+    fs::remove_dir_all(dir.join(TABLE_FILE).with_extension("tmp"))?;
+    pages.try_checkpoint()?;
+    assert!(store()?.unsynced.lock().is_empty());
+    assert_eq!(0, store()?.try_write(7, &frame)?.slot());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[derive(Debug)]
+  struct Epoch(usize);
+
+  impl PageFence for Epoch {
+    fn try_check(&self) -> Result<usize> {
+      Ok(self.0)
+    }
+  }
+
+  #[test]
+  fn test_writes_below_a_seen_epoch_are_fenced() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-store-fence-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let frame = vec![3u8; page_class::size_of(12)];
+    let stale = PageStore::try_open(&dir)?.with_fence(Arc::new(Epoch(1)));
+    let current = PageStore::try_open(&dir)?.with_fence(Arc::new(Epoch(2)));
+
+    stale.try_write(3, &frame)?;
+    let location = current.try_write(5, &frame)?;
+
+    // Reading the newer owner's page fences the older one off
+    let mut read = vec![0u8; frame.len()];
+    stale.try_read_at(5, location, &mut read)?;
+
+    assert_eq!(2, stale.epoch());
+    assert!(stale.try_write(3, &frame).is_err());
+    assert!(current.try_write(3, &frame).is_ok());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_sealed_pages_open_across_key_rotation() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-page-store-sealed-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let frame = vec![5u8; page_class::size_of(12)];
+    let cipher = Arc::new(ChaChaPageCipher::try_new(1, [1u8; KEY_LEN])?);
+    let store = PageStore::try_open_with(&dir, Some(cipher.clone()))?;
+
+    let first = store.try_write(3, &frame)?;
+    cipher.try_rotate(2, [2u8; KEY_LEN])?;
+    let second = store.try_write(5, &frame)?;
+
+    // Nothing of the page is left in the clear
+    let mut stored = vec![0u8; frame.len()];
+    store.try_extent(second)?.read_exact_at(&mut stored, second.data_offset() as u64)?;
+    assert_ne!(frame, stored);
+
+    let mut read = vec![0u8; frame.len()];
+    for pid in [3, 5] {
+      store.try_read(pid, &mut read)?;
+      assert_eq!(frame, read);
+    }
+
+    // A flipped bit or a slot read as another page fails verification
+    store.try_extent(first)?.write_all_at(&[stored[0] ^ 1], first.data_offset() as u64)?;
+    assert!(store.try_read(3, &mut read).is_err());
+    assert!(store.try_read_at(3, second, &mut read).is_err());
+
+    // Without the older key only the pages written after the rotation open
+    let rotated = Arc::new(ChaChaPageCipher::try_new(2, [2u8; KEY_LEN])?);
+    let reopened = PageStore::try_open_with(&dir, Some(rotated))?;
+    assert!(reopened.try_read_at(5, second, &mut read).is_ok());
+    assert!(reopened.try_read_at(3, first, &mut read).is_err());
+
+    // Stores sharing a cipher never share a nonce
+    let other = PageStore::try_open_with(dir.join("other"), Some(cipher))?;
+    assert_ne!(store.sequence().store_id(), other.sequence().store_id());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
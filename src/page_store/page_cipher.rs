@@ -0,0 +1,101 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use chacha20poly1305::{
+  aead::{ AeadInPlace, KeyInit },
+  Key, Tag, XChaCha20Poly1305, XNonce
+};
+
+use parking_lot::RwLock;
+
+use std::{
+  collections::HashMap,
+  fmt::Debug,
+  sync::atomic::{ AtomicU32, Ordering }
+};
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 24;
+pub const TAG_LEN: usize = 16;
+
+// Key id 0 marks a page that was written in the clear
+pub const PLAIN_KEY_ID: u32 = 0;
+
+//
+// Pages are sealed with the current key and opened with whichever key id the
+//  page was written under so keys can be rotated without rewriting every page
+//
+pub trait PageCipher: Debug + Send + Sync {
+  fn key_id(&self) -> u32;
+  fn try_seal(&self, key_id: u32, nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8]) -> Result<[u8; TAG_LEN]>;
+  fn try_open(&self, key_id: u32, nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8], tag: &[u8; TAG_LEN]) -> Result<()>;
+}
+
+//
+// Store ID, PID, write sequence. A cipher and its keys can be shared by
+//  several stores, each with its own write sequence, so the random store ID
+//  keeps their nonces apart and the sequence never repeats within a store
+//
+pub fn page_nonce(store_id: u64, pid: usize, lsn: u64) -> [u8; NONCE_LEN] {
+  let mut nonce = [0u8; NONCE_LEN];
+  nonce[0..8].copy_from_slice(&store_id.to_be_bytes());
+  nonce[8..16].copy_from_slice(&(pid as u64).to_be_bytes());
+  nonce[16..24].copy_from_slice(&lsn.to_be_bytes());
+  nonce
+}
+
+#[derive(Debug)]
+pub struct ChaChaPageCipher(AtomicU32, RwLock<HashMap<u32, [u8; KEY_LEN]>>);
+
+impl ChaChaPageCipher {
+  pub fn add_key(&self, key_id: u32, key: [u8; KEY_LEN]) -> Result<()> {
+    if key_id == PLAIN_KEY_ID {
+      return Err(anyhow!("Key id {} is reserved for unencrypted pages", PLAIN_KEY_ID))
+    }
+
+    self.1.write().insert(key_id, key);
+    Ok(())
+  }
+
+  // New writes use the key, pages written under older keys stay readable
+  pub fn try_rotate(&self, key_id: u32, key: [u8; KEY_LEN]) -> Result<()> {
+    self.add_key(key_id, key)?;
+    self.0.store(key_id, Ordering::Release);
+    Ok(())
+  }
+
+  pub fn try_new(key_id: u32, key: [u8; KEY_LEN]) -> Result<Self> {
+    let cipher = Self(AtomicU32::new(key_id), RwLock::new(HashMap::new()));
+    cipher.add_key(key_id, key)?;
+    Ok(cipher)
+  }
+
+  // XChaCha20-Poly1305 for nonces large enough to hold the store, page and sequence
+  fn try_cipher(&self, key_id: u32) -> Result<XChaCha20Poly1305> {
+    match self.1.read().get(&key_id) {
+      Some(key) => Ok(XChaCha20Poly1305::new(Key::from_slice(key))),
+      None => Err(anyhow!("Page key {} not found", key_id))
+    }
+  }
+}
+
+impl PageCipher for ChaChaPageCipher {
+  fn key_id(&self) -> u32 {
+    self.0.load(Ordering::Acquire)
+  }
+
+  fn try_seal(&self, key_id: u32, nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8]) -> Result<[u8; TAG_LEN]> {
+    match self.try_cipher(key_id)?.encrypt_in_place_detached(XNonce::from_slice(nonce), aad, data) {
+      Ok(tag) => Ok(tag.into()),
+      Err(_) => Err(anyhow!("Failed to encrypt page with key {}", key_id))
+    }
+  }
+
+  fn try_open(&self, key_id: u32, nonce: &[u8; NONCE_LEN], aad: &[u8], data: &mut [u8], tag: &[u8; TAG_LEN]) -> Result<()> {
+    match self.try_cipher(key_id)?.decrypt_in_place_detached(XNonce::from_slice(nonce), aad, data, Tag::from_slice(tag)) {
+      Ok(_) => Ok(()),
+      Err(_) => Err(anyhow!("Page failed verification with key {}", key_id))
+    }
+  }
+}
@@ -0,0 +1,104 @@
+use anyhow::{
+  anyhow, Result
+};
+
+// Compressed frames are only kept when they save at least 1/8th of the frame
+pub const MIN_SAVINGS_SHIFT: usize = 3;
+
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PageCodec {
+  #[default]
+  None,
+  Lz4,
+  Zstd(i32)
+}
+
+impl PageCodec {
+  pub fn id(&self) -> usize {
+    match self {
+      Self::None => 0,
+      Self::Lz4 => 1,
+      Self::Zstd(_) => 2
+    }
+  }
+
+  // The level only matters when compressing so it isn't persisted
+  pub fn try_from_id(id: usize) -> Result<Self> {
+    match id {
+      0 => Ok(Self::None),
+      1 => Ok(Self::Lz4),
+      2 => Ok(Self::Zstd(DEFAULT_ZSTD_LEVEL)),
+      _ => Err(anyhow!("Unknown page codec id {}", id))
+    }
+  }
+
+  // Returns None when the frame doesn't compress well enough to be worth it
+  pub fn try_compress(&self, frame: &[u8]) -> Result<Option<Vec<u8>>> {
+    let compressed = match self {
+      Self::None => return Ok(None),
+      Self::Lz4 => lz4_flex::compress(frame),
+      Self::Zstd(level) => zstd::bulk::compress(frame, *level)?
+    };
+
+    if compressed.len() <= frame.len() - (frame.len() >> MIN_SAVINGS_SHIFT) {
+      Ok(Some(compressed))
+    } else {
+      Ok(None)
+    }
+  }
+
+  pub fn try_decompress(&self, data: &[u8], frame: &mut [u8]) -> Result<()> {
+    let len = match self {
+      Self::None => {
+        frame.copy_from_slice(data);
+        data.len()
+      }
+
+      Self::Lz4 => lz4_flex::decompress_into(data, frame)?,
+      Self::Zstd(_) => zstd::bulk::decompress_to_buffer(data, frame)?
+    };
+
+    if len == frame.len() {
+      Ok(())
+    } else {
+      Err(anyhow!("Decompressed {} bytes into a {} byte frame", len, frame.len()))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compress_round_trip() -> Result<()> {
+    let frame: Vec<u8> = (0..4096).map(|i| (i / 64) as u8).collect();
+
+    for codec in [PageCodec::Lz4, PageCodec::Zstd(DEFAULT_ZSTD_LEVEL)] {
+      let compressed = codec.try_compress(&frame)?.ok_or_else(|| anyhow!("{:?} didn't compress", codec))?;
+      assert!(compressed.len() < frame.len());
+
+      let mut decompressed = vec![0u8; frame.len()];
+      PageCodec::try_from_id(codec.id())?.try_decompress(&compressed, &mut decompressed)?;
+      assert_eq!(frame, decompressed);
+
+      // A frame of the wrong length is an error, not a partial page
+      assert!(codec.try_decompress(&compressed, &mut vec![0u8; frame.len() / 2]).is_err());
+    }
+
+    // Noise doesn't save enough to be stored compressed
+    let mut state = 0x9e3779b97f4a7c15u64;
+    let noise: Vec<u8> = (0..4096).map(|_| {
+      state ^= state << 13;
+      state ^= state >> 7;
+      state ^= state << 17;
+      (state >> 32) as u8
+    }).collect();
+    assert_eq!(None, PageCodec::Lz4.try_compress(&noise)?);
+    assert_eq!(None, PageCodec::None.try_compress(&frame)?);
+
+    Ok(())
+  }
+}
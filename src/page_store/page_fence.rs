@@ -0,0 +1,7 @@
+use anyhow::Result;
+use std::fmt::Debug;
+
+// Checked before every page write, returns the fencing token the write is made under
+pub trait PageFence: Debug + Send + Sync {
+  fn try_check(&self) -> Result<usize>;
+}
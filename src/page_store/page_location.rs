@@ -0,0 +1,110 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use crate::page_class;
+
+use super::{ PageCodec, SLOT_HEADER_LEN };
+
+// Every class file is split into extents of this many bytes
+pub const EXTENT_LEN: usize = 2usize.pow(32);
+
+// PID, CID, Extent, Offset, Codec, Stored Length as big-endian words
+pub const LOCATION_LEN: usize = 48;
+
+//
+// A page always owns a full slot of its class, a slot header followed by
+//  the page, but only the first stored_len bytes of the page are written
+//  when it is compressed which leaves the rest of the slot as a hole
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PageLocation(usize, usize, usize, PageCodec, usize);
+
+impl PageLocation {
+  pub fn cid(&self) -> usize {
+    self.0
+  }
+
+  pub fn extent(&self) -> usize {
+    self.1
+  }
+
+  pub fn offset(&self) -> usize {
+    self.2
+  }
+
+  pub fn codec(&self) -> PageCodec {
+    self.3
+  }
+
+  pub fn stored_len(&self) -> usize {
+    self.4
+  }
+
+  pub fn len(&self) -> usize {
+    page_class::size_of(self.cid())
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn slot(&self) -> usize {
+    self.extent() * Self::slots_per_extent(self.cid()) + self.offset() / Self::slot_len(self.cid())
+  }
+
+  // Where the stored page bytes start, right after the slot header
+  pub fn data_offset(&self) -> usize {
+    self.offset() + SLOT_HEADER_LEN
+  }
+
+  pub fn is_compressed(&self) -> bool {
+    self.codec() != PageCodec::None
+  }
+
+  pub fn with_encoding(&self, codec: PageCodec, stored_len: usize) -> Self {
+    Self(self.0, self.1, self.2, codec, stored_len)
+  }
+
+  pub fn new(cid: usize, extent: usize, offset: usize) -> Self {
+    Self(cid, extent, offset, PageCodec::None, page_class::size_of(cid))
+  }
+
+  pub fn from_slot(cid: usize, slot: usize) -> Self {
+    let per_extent = Self::slots_per_extent(cid);
+    Self::new(cid, slot / per_extent, (slot % per_extent) * Self::slot_len(cid))
+  }
+
+  pub fn slot_len(cid: usize) -> usize {
+    SLOT_HEADER_LEN + page_class::size_of(cid)
+  }
+
+  // Slots never straddle two extents
+  pub fn slots_per_extent(cid: usize) -> usize {
+    (EXTENT_LEN / Self::slot_len(cid)).max(1)
+  }
+
+  pub fn to_bytes(&self, pid: usize) -> [u8; LOCATION_LEN] {
+    let words = [pid, self.cid(), self.extent(), self.offset(), self.codec().id(), self.stored_len()];
+
+    let mut bytes = [0u8; LOCATION_LEN];
+    for (idx, word) in words.iter().enumerate() {
+      bytes[idx * 8 .. (idx + 1) * 8].copy_from_slice(&(*word as u64).to_be_bytes());
+    }
+
+    bytes
+  }
+
+  pub fn try_from_bytes(bytes: &[u8]) -> Result<(usize, Self)> {
+    if bytes.len() < LOCATION_LEN {
+      return Err(anyhow!("Page location requires {} bytes but found {}", LOCATION_LEN, bytes.len()))
+    }
+
+    let word = |idx: usize| -> Result<usize> {
+      Ok(u64::from_be_bytes(bytes[idx * 8 .. (idx + 1) * 8].try_into()?) as usize)
+    };
+
+    let codec = PageCodec::try_from_id(word(4)?)?;
+    Ok((word(0)?, Self(word(1)?, word(2)?, word(3)?, codec, word(5)?)))
+  }
+}
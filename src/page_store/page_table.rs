@@ -0,0 +1,135 @@
+use std::collections::{ HashMap, HashSet };
+
+use crate::{
+  MAX_CLASS_ID, MIN_CLASS_ID, page_class
+};
+
+use super::{ PageLocation, SlotPool };
+
+//
+// Maps every persisted PID to the class file slot that holds it. The table
+//  itself is stored in pages of the store it belongs to, the root lists
+//  where those pages went at the last checkpoint
+//
+// Pages are never written over in place: a write goes to a reserved slot and
+//  is then published as the page's location. A slot the last checkpointed
+//  table may still point at is only released once the next checkpoint is
+//  durable, everything else goes back to its pool right away
+//
+
+// Locations, slots per class, the root, slots released at the next checkpoint and PIDs published since the last one
+#[derive(Debug)]
+pub struct PageTable(HashMap<usize, PageLocation>, Vec<SlotPool>, Vec<(usize, PageLocation)>, Vec<PageLocation>, HashSet<usize>);
+
+impl PageTable {
+  fn locations(&self) -> &HashMap<usize, PageLocation> {
+    &self.0
+  }
+
+  fn slots_mut(&mut self, cid: usize) -> &mut SlotPool {
+    &mut self.1[page_class::index_of(cid)]
+  }
+
+  pub fn len(&self) -> usize {
+    self.locations().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // The pages the table was stored in at the last checkpoint
+  pub fn root(&self) -> &[(usize, PageLocation)] {
+    &self.2
+  }
+
+  pub fn set_root(&mut self, root: Vec<(usize, PageLocation)>) {
+    self.2 = root;
+  }
+
+  pub fn get(&self, pid: usize) -> Option<PageLocation> {
+    self.locations().get(&pid).copied()
+  }
+
+  // A slot of the class for a write that isn't published yet
+  pub fn reserve(&mut self, cid: usize) -> PageLocation {
+    PageLocation::from_slot(cid, self.slots_mut(cid).alloc())
+  }
+
+  // Gives back a reserved slot that was never published
+  pub fn release(&mut self, location: PageLocation) {
+    self.slots_mut(location.cid()).free(location.slot());
+  }
+
+  // Makes the written slot the page's location and returns the one it replaced
+  pub fn publish(&mut self, pid: usize, location: PageLocation) -> Option<PageLocation> {
+    let previous = self.0.insert(pid, location);
+    self.retire(pid, previous);
+    self.4.insert(pid);
+    previous
+  }
+
+  pub fn remove(&mut self, pid: usize) -> Option<PageLocation> {
+    let location = self.0.remove(&pid);
+    self.retire(pid, location);
+    self.4.remove(&pid);
+    location
+  }
+
+  //
+  // Takes the slots that are waiting on a checkpoint, they go back to their
+  //  pools through release_all once the table written after this call is
+  //  durable. Pages published from here on are in that table
+  //
+  pub fn take_retired(&mut self) -> Vec<PageLocation> {
+    self.4.clear();
+    std::mem::take(&mut self.3)
+  }
+
+  pub fn release_all(&mut self, locations: Vec<PageLocation>) {
+    for location in locations {
+      self.release(location);
+    }
+  }
+
+  // Puts slots back on the wait when the checkpoint that took them failed
+  pub fn restore_retired(&mut self, locations: Vec<PageLocation>) {
+    self.3.extend(locations);
+  }
+
+  pub fn entries(&self) -> Vec<(usize, PageLocation)> {
+    let mut entries: Vec<_> = self.locations().iter().map(|(pid, location)| (*pid, *location)).collect();
+    entries.sort_by_key(|(pid, _)| *pid);
+    entries
+  }
+
+  pub fn new() -> Self {
+    Self::from_entries(vec![], vec![])
+  }
+
+  // The root's pages are part of the table like any other page
+  pub fn from_entries(entries: Vec<(usize, PageLocation)>, root: Vec<(usize, PageLocation)>) -> Self {
+    let entries: Vec<(usize, PageLocation)> = entries.into_iter().chain(root.iter().copied()).collect();
+    let slots = (MIN_CLASS_ID..=MAX_CLASS_ID).map(|cid| {
+      SlotPool::from_used(entries.iter().filter(|(_, location)| location.cid() == cid).map(|(_, location)| location.slot()))
+    }).collect();
+
+    Self(entries.into_iter().collect(), slots, root, vec![], HashSet::new())
+  }
+
+  // Private Helpers
+
+  fn retire(&mut self, pid: usize, location: Option<PageLocation>) {
+    match location {
+      Some(location) if self.4.contains(&pid) => self.release(location),
+      Some(location) => self.3.push(location),
+      None => {}
+    }
+  }
+}
+
+impl Default for PageTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
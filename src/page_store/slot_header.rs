@@ -0,0 +1,71 @@
+use anyhow::Result;
+
+use super::{ PLAIN_KEY_ID, TAG_LEN };
+
+// Key ID, Epoch, LSN, Tag
+pub const SLOT_HEADER_LEN: usize = 32;
+
+//
+// Every slot starts with this header followed by the stored page bytes
+//
+// +-----------------------------------------------------------+
+// | Field    | Bits |  Description                            |
+// |----------+------+-----------------------------------------|
+// | Key ID   |   32 |  Key the page was sealed with, 0 if not |
+// | Epoch    |   32 |  Fencing token the page was written at   |
+// | LSN      |   64 |  Write sequence the nonce derives from  |
+// | Tag      |  128 |  Authentication tag of the sealed page  |
+// +-----------------------------------------------------------+
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotHeader(u32, u32, u64, [u8; TAG_LEN]);
+
+impl SlotHeader {
+  pub fn key_id(&self) -> u32 {
+    self.0
+  }
+
+  pub fn epoch(&self) -> u32 {
+    self.1
+  }
+
+  pub fn lsn(&self) -> u64 {
+    self.2
+  }
+
+  pub fn tag(&self) -> &[u8; TAG_LEN] {
+    &self.3
+  }
+
+  pub fn is_sealed(&self) -> bool {
+    self.key_id() != PLAIN_KEY_ID
+  }
+
+  pub fn with_epoch(&self, epoch: u32) -> Self {
+    Self(self.0, epoch, self.2, self.3)
+  }
+
+  pub fn plain() -> Self {
+    Self(PLAIN_KEY_ID, 0, 0, [0u8; TAG_LEN])
+  }
+
+  pub fn sealed(key_id: u32, lsn: u64, tag: [u8; TAG_LEN]) -> Self {
+    Self(key_id, 0, lsn, tag)
+  }
+
+  pub fn to_bytes(&self) -> [u8; SLOT_HEADER_LEN] {
+    let mut bytes = [0u8; SLOT_HEADER_LEN];
+    bytes[0..4].copy_from_slice(&self.key_id().to_be_bytes());
+    bytes[4..8].copy_from_slice(&self.epoch().to_be_bytes());
+    bytes[8..16].copy_from_slice(&self.lsn().to_be_bytes());
+    bytes[16..32].copy_from_slice(self.tag());
+    bytes
+  }
+
+  pub fn try_from_bytes(bytes: &[u8; SLOT_HEADER_LEN]) -> Result<Self> {
+    let key_id = u32::from_be_bytes(bytes[0..4].try_into()?);
+    let epoch = u32::from_be_bytes(bytes[4..8].try_into()?);
+    let lsn = u64::from_be_bytes(bytes[8..16].try_into()?);
+    Ok(Self(key_id, epoch, lsn, bytes[16..32].try_into()?))
+  }
+}
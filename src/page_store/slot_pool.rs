@@ -0,0 +1,34 @@
+use std::collections::BTreeSet;
+
+// Tracks which fixed size slots of a class file are in use
+#[derive(Clone, Debug, Default)]
+pub struct SlotPool(usize, BTreeSet<usize>);
+
+impl SlotPool {
+  pub fn alloc(&mut self) -> usize {
+    match self.1.pop_first() {
+      Some(slot) => slot,
+      None => {
+        self.0 += 1;
+        self.0 - 1
+      }
+    }
+  }
+
+  pub fn free(&mut self, slot: usize) -> bool {
+    if slot < self.0 {
+      self.1.insert(slot)
+    } else {
+      false
+    }
+  }
+
+  // Rebuilds the pool from the slots that are known to be in use
+  pub fn from_used<I: IntoIterator<Item = usize>>(used: I) -> Self {
+    let used: BTreeSet<usize> = used.into_iter().collect();
+    let hwm = used.iter().next_back().map_or(0, |slot| slot + 1);
+    let free = (0..hwm).filter(|slot| !used.contains(slot)).collect();
+    Self(hwm, free)
+  }
+}
+
@@ -0,0 +1,28 @@
+//
+// Where a page should be persisted, see NOTES.md 2021-12-24
+//
+// Index  - B+Tree inner nodes and other small hot pages (ex: PMEM)
+// Local  - Temporary and worker local pages (ex: NVMe)
+// Shared - Committed pages every node in the pod must see (ex: EFS)
+//
+
+pub const STORAGE_TIERS: [StorageTier; 3] = [StorageTier::Index, StorageTier::Local, StorageTier::Shared];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StorageTier {
+  Index,
+  #[default]
+  Local,
+  Shared
+}
+
+impl StorageTier {
+  pub fn index(&self) -> usize {
+    match self {
+      Self::Index => 0,
+      Self::Local => 1,
+      Self::Shared => 2
+    }
+  }
+}
+
@@ -0,0 +1,86 @@
+use anyhow::{
+  anyhow, Result
+};
+use parking_lot::Mutex;
+
+use std::{
+  fs::{ self, File },
+  io::{ Read, Write },
+  path::{ Path, PathBuf },
+  sync::atomic::{ AtomicU64, Ordering }
+};
+
+// Sequence numbers reserved with each durable write of the high-water mark
+pub const SEQUENCE_BLOCK_LEN: u64 = 2u64.pow(20);
+
+//
+// Stands in for an LSN until we have a log, every page write takes the
+//  next number so sealed pages never reuse a nonce, even across restarts
+//
+// The file holds the random ID of the store the sequence belongs to and the
+//  high-water mark
+//
+
+// Next, high-water mark, file and store ID
+#[derive(Debug)]
+pub struct WriteSequence(AtomicU64, AtomicU64, Mutex<PathBuf>, u64);
+
+impl WriteSequence {
+  pub fn store_id(&self) -> u64 {
+    self.3
+  }
+
+  pub fn try_next(&self) -> Result<u64> {
+    let lsn = self.0.fetch_add(1, Ordering::SeqCst);
+
+    if lsn < self.1.load(Ordering::Acquire) {
+      return Ok(lsn)
+    }
+
+    let path = self.2.lock();
+    if lsn >= self.1.load(Ordering::Acquire) {
+      let hwm = lsn + SEQUENCE_BLOCK_LEN;
+      Self::try_write_hwm(&path, self.store_id(), hwm)?;
+      self.1.store(hwm, Ordering::Release);
+    }
+
+    Ok(lsn)
+  }
+
+  pub fn try_open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref().to_path_buf();
+
+    // Nothing below the high-water mark is safe to use again
+    let (store_id, hwm) = if path.exists() {
+      let bytes = fs::read(&path)?;
+      let store_id = bytes.get(0..8).ok_or_else(|| anyhow!("Write sequence {} is truncated", path.display()))?;
+      let hwm = bytes.get(8..16).ok_or_else(|| anyhow!("Write sequence {} is truncated", path.display()))?;
+      (u64::from_be_bytes(store_id.try_into()?), u64::from_be_bytes(hwm.try_into()?))
+    } else {
+      let store_id = Self::try_random_id()?;
+      Self::try_write_hwm(&path, store_id, 1)?;
+      (store_id, 1)
+    };
+
+    Ok(Self(AtomicU64::new(hwm), AtomicU64::new(hwm), Mutex::new(path), store_id))
+  }
+
+  fn try_random_id() -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(u64::from_ne_bytes(bytes))
+  }
+
+  fn try_write_hwm(path: &Path, store_id: u64, hwm: u64) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      tmp.write_all(&store_id.to_be_bytes())?;
+      tmp.write_all(&hwm.to_be_bytes())?;
+      tmp.sync_all()?;
+    }
+
+    Ok(fs::rename(&tmp_path, path)?)
+  }
+}
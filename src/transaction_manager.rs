@@ -0,0 +1,868 @@
+mod commit_log;
+mod read_predicate;
+mod savepoint;
+mod transaction;
+mod txn_write_guard;
+mod undo_buffer;
+mod version_vector;
+mod write_intents;
+
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::{ Mutex, RwLock };
+
+use std::{
+  collections::BTreeMap,
+  io::Read,
+  sync::{
+    Arc,
+    atomic::{ AtomicUsize, Ordering }
+  },
+  thread::{ self, JoinHandle },
+  time::Duration
+};
+
+use crate::{
+  PageChain, PageGuard, PageManager, TXN_ID_BASE, Wal, WalRecord
+};
+
+pub use commit_log::*;
+pub use read_predicate::*;
+pub use savepoint::*;
+pub use transaction::*;
+pub use txn_write_guard::*;
+pub use undo_buffer::*;
+pub use version_vector::*;
+pub use write_intents::*;
+
+//
+// Issues the two system-wide counters from docs/acid/mvcc-hyper.md: logical
+//  time starts at 0 and transaction ids start at 2^63 so an id is always
+//  larger than any time and a stamp tells committed and uncommitted apart
+//
+// A transaction reads the versions committed up to the time it started at,
+//  its writes are stamped with its id until it commits and they are stamped
+//  with its commit time. Commits take turns so a snapshot never sees half of
+//  a commit's pages restamped
+//
+
+//
+// With a write-ahead log every write is logged before it goes into the page
+//  and the first LSN of every transaction that wrote something is kept in
+//  the active transaction table, which together with the page manager's
+//  dirty page table is what a fuzzy checkpoint copies into the log
+//
+
+//
+// Undo buffers of committed transactions are kept until every snapshot sees
+//  what they committed, then they are unlinked from the version vectors and
+//  freed. Anything that follows or changes version pointers holds the undo
+//  lock shared and the collection holds it exclusively
+//
+
+#[derive(Debug)]
+pub struct TransactionManager {
+  pages: Arc<PageManager>,
+  // Logical time, the commit time of the latest commit
+  time: AtomicUsize,
+  next_id: AtomicUsize,
+  // Start time of every active transaction by id
+  active: RwLock<BTreeMap<usize, usize>>,
+  commits: Mutex<()>,
+  committed_undo: Mutex<Vec<(usize, UndoBuffer)>>,
+  commit_log: CommitLog,
+  intents: WriteIntents,
+  wal: Option<Arc<Wal>>,
+  // First LSN of every active transaction that logged a write
+  first_lsns: Mutex<BTreeMap<usize, usize>>,
+  undo_links: RwLock<()>
+}
+
+impl TransactionManager {
+  pub fn pages(&self) -> &Arc<PageManager> {
+    &self.pages
+  }
+
+  pub fn time(&self) -> usize {
+    self.time.load(Ordering::Acquire)
+  }
+
+  pub fn commit_log(&self) -> &CommitLog {
+    &self.commit_log
+  }
+
+  pub fn intents(&self) -> &WriteIntents {
+    &self.intents
+  }
+
+  pub fn wal(&self) -> Option<&Arc<Wal>> {
+    self.wal.as_ref()
+  }
+
+  // Transaction id and the LSN of its first logged write, oldest first
+  pub fn active_lsns(&self) -> Vec<(usize, usize)> {
+    let mut active: Vec<(usize, usize)> = self.first_lsns().lock().iter().map(|(txn, lsn)| (*txn, *lsn)).collect();
+    active.sort_unstable_by_key(|(txn, lsn)| (*lsn, *txn));
+    active
+  }
+
+  pub fn active_len(&self) -> usize {
+    self.active().read().len()
+  }
+
+  pub fn is_active(&self, id: usize) -> bool {
+    self.active().read().contains_key(&id)
+  }
+
+  // Snapshot of the oldest transaction still running
+  pub fn oldest_active(&self) -> Option<usize> {
+    self.active().read().values().min().copied()
+  }
+
+  pub fn begin(&self) -> Transaction {
+    let id = self.next_id.fetch_add(1, Ordering::AcqRel);
+
+    // Registered before the time is read so the version GC never gets ahead of it
+    let _commit = self.commits().lock();
+    let started_at = self.time();
+    self.pages().versions().register(started_at);
+    self.active().write().insert(id, started_at);
+
+    Transaction::new(id, started_at)
+  }
+
+  // The version of the page the transaction sees, None when the page is newer than its snapshot
+  pub fn try_fetch(&self, txn: &Transaction, pid: usize) -> Result<Option<PageGuard<'_>>> {
+    self.pages().try_fetch_visible(pid, txn.started_at(), txn.id())
+  }
+
+  //
+  // A version of the page only this transaction sees until it commits. The
+  //  current bytes become the version everyone else reads, so a page the
+  //  transaction already wrote in place can't be copied: its uncommitted
+  //  bytes would go along. Once the head is the transaction's own version
+  //  in-place writes land in that and copying it again is fine
+  //
+  pub fn try_cow<'a>(&'a self, txn: &mut Transaction, page: PageGuard<'a>) -> Result<PageGuard<'a>> {
+    self.try_check_active(txn)?;
+
+    if page.chain().stamp() != txn.id() && txn.undo().is_undoing(page.pid()) {
+      return Err(anyhow!("Page {} was written in place by transaction {} and can't be copied on write", page.pid(), txn.id()))
+    }
+    self.try_claim(txn, page.pid(), 0, page.data_len())?;
+    self.try_check_snapshot(txn, page.pid(), page.chain().stamp())?;
+
+    // Written before the newest savepoint, which has to be able to get this version back
+    let page = if page.chain().stamp() == txn.id() && !txn.writes_since_savepoint().contains(&page.pid()) {
+      self.pages().try_fork(page, txn.id())?
+    } else {
+      self.pages().try_cow(page, txn.id())?
+    };
+
+    txn.record_write(page.pid());
+
+    Ok(page)
+  }
+
+  // The value in the slot as of the transaction's snapshot
+  pub fn try_read_value(&self, txn: &Transaction, page: &mut PageGuard, vector: VersionVector, slot: usize) -> Result<Vec<u8>> {
+    let _links = self.undo_links().read();
+    vector.try_read_visible(self.pages(), page, slot, txn.started_at(), txn.id())
+  }
+
+  // Updates the value in place after saving what it replaced in the transaction's undo buffer
+  pub fn try_update(&self, txn: &mut Transaction, page: &mut PageGuard, vector: VersionVector, slot: usize, value: &[u8]) -> Result<()> {
+    self.try_check_active(txn)?;
+    self.try_claim(txn, page.pid(), vector.value_offset(slot), vector.value_len())?;
+
+    let links = self.undo_links().read();
+    let (before, pointer) = vector.try_slot(page, slot)?;
+
+    // The newest entry is stamped by whoever wrote the value in place, rolling back takes the undo lock again
+    let stamp = if pointer == 0 { 0 } else { UndoEntry::try_read(self.pages(), pointer)?.stamp() };
+    if Self::is_after_snapshot(txn, stamp) {
+      drop(links);
+      return self.try_check_snapshot(txn, page.pid(), stamp)
+    }
+
+    let entry = UndoEntry::new(txn.id(), page.pid(), pointer, vector.value_offset(slot), vector.pointer_offset(slot), &before);
+    let undo = txn.undo_mut().try_push(self.pages(), &entry)?;
+
+    vector.try_update(page, slot, value, undo)?;
+    txn.record_image(WriteImage::new(vector.attribute(), &before, value));
+
+    Ok(())
+  }
+
+  // A guard whose every write goes through try_write
+  pub fn try_write_guard<'t, 'g, 'a>(&'t self, txn: &'t mut Transaction, page: &'g mut PageGuard<'a>) -> Result<TxnWriteGuard<'t, 'g, 'a>> {
+    self.try_check_active(txn)?;
+    Ok(TxnWriteGuard::new(self, txn, page))
+  }
+
+  //
+  // Writes into the page in place after claiming the bytes for the
+  //  transaction, saving what they replace in its undo buffer and logging
+  //  them. Aborting or rolling back to an earlier savepoint puts the old
+  //  bytes back
+  //
+  pub fn try_write<R: Read>(&self, txn: &mut Transaction, page: &mut PageGuard, offset: usize, len: usize, data: &mut R) -> Result<usize> {
+    self.try_check_active(txn)?;
+    self.try_claim(txn, page.pid(), offset, len)?;
+
+    let mut redo = vec![];
+    data.take(len as u64).read_to_end(&mut redo)?;
+
+    let mut before = vec![];
+    page.try_write()?.read(offset, redo.len(), &mut before)?;
+    {
+      let _links = self.undo_links().read();
+      let entry = UndoEntry::new(txn.id(), page.pid(), 0, offset, UNDO_NO_POINTER, &before);
+      txn.undo_mut().try_push(self.pages(), &entry)?;
+    }
+
+    txn.record_write(page.pid());
+
+    let lsn = match self.wal() {
+      Some(wal) => {
+        let lsn = wal.try_append(&WalRecord::Update(txn.id(), page.pid(), offset, redo.clone()))?;
+        self.first_lsns().lock().entry(txn.id()).or_insert(lsn);
+        Some(lsn)
+      }
+
+      None => None
+    };
+
+    let written = page.try_write()?.write(offset, redo.len(), &mut redo.as_slice())?;
+    if let Some(lsn) = lsn {
+      self.pages().dirty_pages().mark(page.pid(), lsn);
+    }
+
+    Ok(written)
+  }
+
+  //
+  // Validates the transaction's reads against everything committed since it
+  //  started, aborting it on a conflict, then stamps every version it wrote
+  //  with its commit time and returns that time
+  //
+  pub fn try_commit(&self, mut txn: Transaction) -> Result<usize> {
+    self.try_check_active(&txn)?;
+
+    let commit = self.commits().lock();
+    if let Err(err) = self.commit_log().try_validate(txn.started_at(), txn.predicates()) {
+      drop(commit);
+      self.try_abort(txn)?;
+      return Err(err)
+    }
+
+    // Bytes written through try_write or copied on write have no attribute, every read predicate conflicts with them
+    if !txn.writes().is_empty() {
+      txn.record_image(WriteImage::opaque());
+    }
+
+    let committed_at = self.time() + 1;
+
+    for pid in txn.writes() {
+      self.pages().try_squash(*pid, txn.id())?;
+
+      let page = self.pages().try_fetch(*pid)?;
+      if page.chain().stamp() == txn.id() {
+        page.chain().set_stamp(committed_at);
+      }
+    }
+
+    txn.undo().try_stamp(self.pages(), committed_at)?;
+    self.try_log_end(&txn, WalRecord::Commit(txn.id()))?;
+    self.time.store(committed_at, Ordering::Release);
+    self.intents().commit(txn.id(), committed_at);
+    self.finish(&txn);
+
+    let (undo, images) = txn.into_parts();
+    self.commit_log().record(committed_at, images);
+    drop(commit);
+
+    self.pages().versions().advance(committed_at);
+    self.prune(self.oldest_active().unwrap_or(committed_at));
+
+    if !undo.is_empty() {
+      self.committed_undo().lock().push((committed_at, undo));
+    }
+
+    self.try_collect_undo()?;
+    Ok(committed_at)
+  }
+
+  // Unlinks and frees the undo buffers of commits every snapshot sees, returns how many went
+  pub fn try_collect_undo(&self) -> Result<usize> {
+    let horizon = self.pages().versions().horizon();
+    let _links = self.undo_links().write();
+
+    let mut ready = {
+      let mut committed = self.committed_undo().lock();
+      let (ready, kept) = std::mem::take(&mut *committed).into_iter().partition::<Vec<_>, _>(|(at, _)| *at <= horizon);
+      *committed = kept;
+      ready.into_iter()
+    };
+
+    let mut collected = 0;
+    while let Some((at, undo)) = ready.next() {
+      // Whatever is left is collected next time
+      if let Err(err) = undo.try_unlink(self.pages()) {
+        self.committed_undo().lock().extend([(at, undo)].into_iter().chain(ready));
+        return Err(err)
+      }
+
+      undo.try_free(self.pages())?;
+      collected += 1;
+    }
+
+    Ok(collected)
+  }
+
+  //
+  // Undoes the updates and page versions that came after the savepoint and
+  //  keeps everything before it, write intents stay with the transaction
+  //
+  pub fn try_rollback_to(&self, txn: &mut Transaction, savepoint: Savepoint) -> Result<()> {
+    self.try_check_active(txn)?;
+
+    if !txn.savepoints().contains(&savepoint) {
+      return Err(anyhow!("Savepoint {} of transaction {} was rolled back past", savepoint.id(), txn.id()))
+    }
+
+    {
+      let _links = self.undo_links().read();
+      txn.undo_mut().try_rollback_to(self.pages(), savepoint.undo_len())?;
+    }
+
+    for pid in txn.writes()[savepoint.writes_len()..].iter().rev() {
+      self.pages().try_rollback(*pid, txn.id())?;
+    }
+
+    txn.try_truncate(savepoint)
+  }
+
+  // Puts back the versions the transaction replaced, a transaction that lost a write conflict is already aborted
+  pub fn try_abort(&self, mut txn: Transaction) -> Result<()> {
+    if !self.is_active(txn.id()) {
+      return Ok(())
+    }
+
+    self.try_rollback(&mut txn)
+  }
+
+  //
+  // Takes a fuzzy checkpoint without stopping any worker: BEGIN_CHECKPOINT,
+  //  then END_CHECKPOINT with copies of the dirty page table and the active
+  //  transaction table. Once the checkpoint is durable every segment below
+  //  the oldest LSN recovery could still need is deleted. Returns the LSN of
+  //  the END_CHECKPOINT record
+  //
+  pub fn try_checkpoint(&self) -> Result<usize> {
+    let wal = self.wal().ok_or_else(|| anyhow!("Checkpoints need a write-ahead log"))?;
+
+    let begin = wal.try_append(&WalRecord::BeginCheckpoint)?;
+    let dirty = self.pages().dirty_pages().entries();
+    let active = self.active_lsns();
+    let end = wal.try_append(&WalRecord::EndCheckpoint(begin, dirty.clone(), active.clone()))?;
+
+    wal.try_sync()?;
+    wal.try_set_checkpoint(end)?;
+
+    let bound = dirty.iter().chain(&active).map(|(_, lsn)| *lsn).fold(begin, usize::min);
+    wal.try_truncate(bound)?;
+
+    Ok(end)
+  }
+
+  // Takes a checkpoint every interval until the transaction manager is dropped
+  pub fn spawn_checkpoints(self: &Arc<Self>, interval: Duration) -> JoinHandle<Result<()>> {
+    let txns = Arc::downgrade(self);
+
+    thread::spawn(move || {
+      loop {
+        thread::sleep(interval);
+
+        match txns.upgrade() {
+          Some(txns) => txns.try_checkpoint()?,
+          None => return Ok(())
+        };
+      }
+    })
+  }
+
+  pub fn new(pages: Arc<PageManager>) -> Self {
+    Self {
+      pages,
+      time: AtomicUsize::new(0),
+      next_id: AtomicUsize::new(TXN_ID_BASE),
+      active: RwLock::new(BTreeMap::new()),
+      commits: Mutex::new(()),
+      committed_undo: Mutex::new(vec![]),
+      commit_log: CommitLog::new(),
+      intents: WriteIntents::new(),
+      wal: None,
+      first_lsns: Mutex::new(BTreeMap::new()),
+      undo_links: RwLock::new(())
+    }
+  }
+
+  pub fn with_wal(mut self, wal: Arc<Wal>) -> Self {
+    self.wal = Some(wal);
+    self
+  }
+
+  // Private Accessors + Helpers
+
+  fn active(&self) -> &RwLock<BTreeMap<usize, usize>> {
+    &self.active
+  }
+
+  fn commits(&self) -> &Mutex<()> {
+    &self.commits
+  }
+
+  fn committed_undo(&self) -> &Mutex<Vec<(usize, UndoBuffer)>> {
+    &self.committed_undo
+  }
+
+  fn first_lsns(&self) -> &Mutex<BTreeMap<usize, usize>> {
+    &self.first_lsns
+  }
+
+  fn undo_links(&self) -> &RwLock<()> {
+    &self.undo_links
+  }
+
+  // Commits are durable once their record is synced, aborts only have to leave the active transaction table
+  fn try_log_end(&self, txn: &Transaction, record: WalRecord) -> Result<()> {
+    if let Some(wal) = self.wal() {
+      if self.first_lsns().lock().remove(&txn.id()).is_some() {
+        wal.try_append(&record)?;
+
+        if matches!(record, WalRecord::Commit(_)) {
+          wal.try_sync()?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  // The first writer keeps the bytes, the second is aborted on the spot
+  fn try_claim(&self, txn: &mut Transaction, pid: usize, offset: usize, len: usize) -> Result<()> {
+    let claimed = self.intents().try_acquire(txn.id(), txn.started_at(), pid, offset, len);
+
+    if claimed.is_err() {
+      self.try_rollback(txn)?;
+    }
+
+    claimed
+  }
+
+  //
+  // A version committed after the transaction started would be overwritten
+  //  by one based on an older snapshot, a lost update. The stamp is read
+  //  after the bytes were claimed so the writer that committed it can't be
+  //  running anymore
+  //
+  fn try_check_snapshot(&self, txn: &mut Transaction, pid: usize, stamp: usize) -> Result<()> {
+    if !Self::is_after_snapshot(txn, stamp) {
+      return Ok(())
+    }
+
+    self.try_rollback(txn)?;
+    Err(anyhow!("Page {} was committed at {} after transaction {} started at {}", pid, stamp, txn.id(), txn.started_at()))
+  }
+
+  fn is_after_snapshot(txn: &Transaction, stamp: usize) -> bool {
+    !PageChain::is_uncommitted(stamp) && stamp > txn.started_at()
+  }
+
+  // Keeps undoing past a failed step so as little as possible is left stamped with the transaction, the first error is returned
+  fn try_rollback(&self, txn: &mut Transaction) -> Result<()> {
+    let links = self.undo_links().read();
+    let mut rolled_back = txn.undo().try_rollback(self.pages());
+    drop(links);
+
+    for pid in txn.writes().iter().rev() {
+      rolled_back = rolled_back.and(self.pages().try_rollback(*pid, txn.id()));
+    }
+
+    let logged = self.try_log_end(txn, WalRecord::Abort(txn.id()));
+
+    self.finish(txn);
+    rolled_back?;
+    logged?;
+
+    std::mem::take(txn.undo_mut()).try_free(self.pages())
+  }
+
+  fn try_check_active(&self, txn: &Transaction) -> Result<()> {
+    if self.is_active(txn.id()) {
+      Ok(())
+    } else {
+      Err(anyhow!("Transaction {} is not active", txn.id()))
+    }
+  }
+
+  fn finish(&self, txn: &Transaction) {
+    self.active().write().remove(&txn.id());
+    self.intents().release(txn.id());
+    self.pages().versions().release(txn.started_at());
+    self.prune(self.oldest_active().unwrap_or(self.time()));
+  }
+
+  // Forgets the commits every running snapshot already sees
+  fn prune(&self, horizon: usize) {
+    self.commit_log().prune(horizon);
+    self.intents().prune(horizon);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{ PageIdPool, PageStore, PageTiers, StorageTier };
+  use std::{ fs, io::{ self, Cursor }, ops::Bound };
+
+  fn read(txns: &TransactionManager, txn: &Transaction, pid: usize) -> Result<Option<u8>> {
+    match txns.try_fetch(txn, pid)? {
+      Some(mut page) => {
+        let mut data = vec![];
+        page.try_write()?.read(0, 1, &mut data)?;
+        Ok(Some(data[0]))
+      }
+
+      None => Ok(None)
+    }
+  }
+
+  #[test]
+  fn test_commit_and_abort() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let pid = txns.pages().try_alloc(64)?.pid();
+
+    let mut writer = txns.begin();
+    let reader = txns.begin();
+    assert!(writer.id() >= TXN_ID_BASE);
+    assert_eq!(0, writer.started_at());
+
+    let mut page = txns.try_cow(&mut writer, txns.pages().try_fetch(pid)?)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![7u8]))?;
+
+    assert_eq!(Some(7), read(&txns, &writer, pid)?);
+    assert_eq!(Some(0), read(&txns, &reader, pid)?);
+    assert_eq!(1, txns.try_commit(writer)?);
+
+    // The reader keeps its snapshot while new transactions see the commit
+    let later = txns.begin();
+    assert_eq!(Some(0), read(&txns, &reader, pid)?);
+    assert_eq!(Some(7), read(&txns, &later, pid)?);
+
+    let mut aborted = txns.begin();
+    let mut page = txns.try_cow(&mut aborted, txns.pages().try_fetch(pid)?)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![9u8]))?;
+    txns.try_abort(aborted)?;
+
+    assert_eq!(Some(7), read(&txns, &later, pid)?);
+    assert_eq!(2, txns.active_len());
+    assert_eq!(Some(0), txns.oldest_active());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_cow_over_a_newer_commit_aborts() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let pid = txns.pages().try_alloc(64)?.pid();
+
+    let mut writer = txns.begin();
+    let mut stale = txns.begin();
+    let mut page = txns.try_cow(&mut writer, txns.pages().try_fetch(pid)?)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![7u8]))?;
+    drop(page);
+    txns.try_commit(writer)?;
+
+    // Its snapshot still reads the old version so a write would lose the commit
+    assert!(txns.try_cow(&mut stale, txns.pages().try_fetch(pid)?).is_err());
+    assert!(!txns.is_active(stale.id()));
+    assert!(txns.intents().is_empty());
+
+    let mut later = txns.begin();
+    txns.try_cow(&mut later, txns.pages().try_fetch(pid)?)?;
+    txns.try_commit(later)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_cow_of_a_page_written_in_place_is_refused() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let mut page = txns.pages().try_alloc(64)?;
+    let pid = page.pid();
+
+    // The copy everyone else reads would carry the uncommitted byte
+    let mut writer = txns.begin();
+    txns.try_write_guard(&mut writer, &mut page)?.write(0, 1, &mut Cursor::new(vec![7u8]))?;
+    drop(page);
+    assert!(txns.try_cow(&mut writer, txns.pages().try_fetch(pid)?).is_err());
+    txns.try_commit(writer)?;
+
+    // Writes in place into the transaction's own version don't leak into the one under it
+    let mut later = txns.begin();
+    let mut page = txns.try_cow(&mut later, txns.pages().try_fetch(pid)?)?;
+    txns.try_write_guard(&mut later, &mut page)?.write(0, 1, &mut Cursor::new(vec![9u8]))?;
+    drop(page);
+
+    later.savepoint();
+    txns.try_cow(&mut later, txns.pages().try_fetch(pid)?)?;
+    txns.try_commit(later)?;
+
+    assert_eq!(Some(9), read(&txns, &txns.begin(), pid)?);
+    Ok(())
+  }
+
+  #[test]
+  fn test_update_in_place_with_undo() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let vector = VersionVector::new(4, 2);
+
+    let mut page = txns.pages().try_alloc(vector.data_len() as u32)?;
+    vector.try_init(&mut page)?;
+    assert_eq!(None, vector.try_synopsis(&mut page)?);
+
+    let reader = txns.begin();
+    let mut first = txns.begin();
+    txns.try_update(&mut first, &mut page, vector, 2, &[1, 1])?;
+    txns.try_update(&mut first, &mut page, vector, 2, &[2, 2])?;
+
+    let mut second = txns.begin();
+    let conflict = txns.try_update(&mut second, &mut page, vector, 2, &[3, 3]).unwrap_err();
+    assert_eq!(Some(first.id()), conflict.downcast_ref::<WriteConflict>().map(WriteConflict::holder));
+    assert!(!txns.is_active(second.id()));
+    assert!(txns.try_commit(second).is_err());
+
+    assert_eq!(vec![2, 2], txns.try_read_value(&first, &mut page, vector, 2)?);
+    txns.try_update(&mut first, &mut page, vector, 3, &[4, 4])?;
+    txns.try_commit(first)?;
+
+    // The reader still sees the old values so the committed ranges stay claimed
+    assert!(!txns.intents().is_empty());
+
+    let later = txns.begin();
+    let mut second = txns.begin();
+    txns.try_update(&mut second, &mut page, vector, 2, &[3, 3])?;
+    assert_eq!(vec![0, 0], txns.try_read_value(&reader, &mut page, vector, 2)?);
+    assert_eq!(vec![2, 2], txns.try_read_value(&later, &mut page, vector, 2)?);
+
+    txns.try_abort(second)?;
+    assert_eq!(vec![2, 2], txns.try_read_value(&later, &mut page, vector, 2)?);
+    assert_eq!(Some((2, 3)), vector.try_synopsis(&mut page)?);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_stale_updates_abort_and_committed_undo_is_freed() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let vector = VersionVector::new(4, 2);
+
+    let mut page = txns.pages().try_alloc(vector.data_len() as u32)?;
+    vector.try_init(&mut page)?;
+    let used = txns.pages().used_bytes();
+
+    let mut stale = txns.begin();
+    let mut writer = txns.begin();
+    txns.try_update(&mut writer, &mut page, vector, 0, &[1, 1])?;
+    txns.try_commit(writer)?;
+
+    // The stale snapshot still needs the before-image and would overwrite the commit
+    assert_eq!(0, txns.try_collect_undo()?);
+    assert!(txns.try_update(&mut stale, &mut page, vector, 0, &[2, 2]).is_err());
+    assert!(!txns.is_active(stale.id()));
+
+    assert_eq!(1, txns.try_collect_undo()?);
+    txns.pages().try_reclaim()?;
+    assert_eq!(used, txns.pages().used_bytes());
+    assert_eq!((vec![1, 1], 0), vector.try_slot(&mut page, 0)?);
+
+    assert!(try_pack_pointer(1 << 32, 0).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_guarded_writes_claim_and_roll_back() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let mut page = txns.pages().try_alloc(64)?;
+    let bytes = |page: &mut PageGuard| -> Result<Vec<u8>> {
+      let mut data = vec![];
+      page.try_write()?.read(0, 2, &mut data)?;
+      Ok(data)
+    };
+
+    let mut first = txns.begin();
+    let mut second = txns.begin();
+    txns.try_write_guard(&mut first, &mut page)?.write(0, 2, &mut Cursor::new(vec![1u8, 1u8]))?;
+
+    let savepoint = first.savepoint();
+    txns.try_write_guard(&mut first, &mut page)?.write(0, 2, &mut Cursor::new(vec![2u8, 2u8]))?;
+    txns.try_rollback_to(&mut first, savepoint)?;
+    assert_eq!(vec![1, 1], bytes(&mut page)?);
+
+    let conflict = txns.try_write_guard(&mut second, &mut page)?.write(1, 1, &mut Cursor::new(vec![9u8])).unwrap_err();
+    assert_eq!(Some(first.id()), conflict.downcast_ref::<WriteConflict>().map(WriteConflict::holder));
+    assert!(!txns.is_active(second.id()));
+
+    txns.try_abort(first)?;
+    assert_eq!(vec![0, 0], bytes(&mut page)?);
+    assert!(txns.intents().is_empty());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_commit_validates_read_predicates() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let vector = VersionVector::new(4, 8).with_attribute(1);
+
+    let mut page = txns.pages().try_alloc(vector.data_len() as u32)?;
+    vector.try_init(&mut page)?;
+
+    let mut writer = txns.begin();
+    let mut outside = txns.begin();
+    let mut inside = txns.begin();
+
+    txns.try_update(&mut writer, &mut page, vector, 0, &150u64.to_be_bytes())?;
+    outside.read(ReadPredicate::range(1, Bound::Included(200u64.to_be_bytes().to_vec()), Bound::Unbounded));
+    inside.read(ReadPredicate::range(1, Bound::Included(100u64.to_be_bytes().to_vec()), Bound::Unbounded));
+    txns.try_commit(writer)?;
+
+    assert!(txns.try_commit(outside).is_ok());
+    assert!(txns.try_commit(inside).is_err());
+    assert_eq!(0, txns.active_len());
+
+    // Nobody who could conflict with the write is left
+    assert!(txns.commit_log().is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn test_raw_writes_conflict_with_every_predicate() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let mut page = txns.pages().try_alloc(64)?;
+
+    let mut writer = txns.begin();
+    let mut reader = txns.begin();
+    let unread = txns.begin();
+
+    // The bytes belong to no attribute so a predicate on any of them may have seen them
+    txns.try_write_guard(&mut writer, &mut page)?.write(0, 8, &mut Cursor::new(150u64.to_be_bytes()))?;
+    reader.read(ReadPredicate::point(1, &200u64.to_be_bytes()));
+    txns.try_commit(writer)?;
+
+    assert!(txns.try_commit(reader).is_err());
+    assert!(txns.try_commit(unread).is_ok());
+    Ok(())
+  }
+
+  #[test]
+  fn test_rollback_to_savepoint() -> Result<()> {
+    let txns = TransactionManager::new(Arc::new(PageManager::try_new(2usize.pow(31))?));
+    let vector = VersionVector::new(4, 1);
+    let pid = txns.pages().try_alloc(64)?.pid();
+
+    let mut slots = txns.pages().try_alloc(vector.data_len() as u32)?;
+    vector.try_init(&mut slots)?;
+
+    let mut txn = txns.begin();
+    let mut page = txns.try_cow(&mut txn, txns.pages().try_fetch(pid)?)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![1u8]))?;
+    txns.try_update(&mut txn, &mut slots, vector, 0, &[1])?;
+
+    let savepoint = txn.savepoint();
+    let mut page = txns.try_cow(&mut txn, page)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![2u8]))?;
+    txns.try_update(&mut txn, &mut slots, vector, 0, &[2])?;
+    txns.try_update(&mut txn, &mut slots, vector, 1, &[2])?;
+
+    txns.try_rollback_to(&mut txn, savepoint)?;
+    assert_eq!(Some(1), read(&txns, &txn, pid)?);
+    assert_eq!(vec![1], txns.try_read_value(&txn, &mut slots, vector, 0)?);
+    assert_eq!(vec![0], txns.try_read_value(&txn, &mut slots, vector, 1)?);
+
+    // The savepoint stays usable and only the newest version survives the commit
+    let mut page = txns.try_cow(&mut txn, page)?;
+    page.try_write()?.write(0, 1, &mut Cursor::new(vec![3u8]))?;
+    txns.try_commit(txn)?;
+
+    let later = txns.begin();
+    assert_eq!(Some(3), read(&txns, &later, pid)?);
+    assert_eq!(vec![1], txns.try_read_value(&later, &mut slots, vector, 0)?);
+    assert_eq!(0, txns.pages().try_fetch(txns.pages().try_fetch(pid)?.chain().past())?.chain().past());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_fuzzy_checkpoint_truncates_the_log() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("vex-checkpoints-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let tiers = PageTiers::new().with(StorageTier::Local, PageStore::try_open(dir.join("pages"))?);
+    let pages = Arc::new(PageManager::try_new_with(2usize.pow(31), PageIdPool::new(), tiers)?);
+    let wal = Arc::new(Wal::try_open_with(dir.join("wal"), 256)?);
+    let txns = TransactionManager::new(pages.clone()).with_wal(wal.clone());
+
+    let mut first = pages.try_alloc(64)?;
+    let mut second = pages.try_alloc(64)?;
+
+    let mut writer = txns.begin();
+    txns.try_write(&mut writer, &mut first, 0, 100, &mut io::repeat(1))?;
+    let first_lsn = pages.dirty_pages().rec_lsn(first.pid()).unwrap_or_default();
+    txns.try_commit(writer)?;
+
+    // Still running when the checkpoint is taken
+    let mut running = txns.begin();
+    txns.try_write(&mut running, &mut second, 0, 100, &mut io::repeat(2))?;
+    let running_lsn = pages.dirty_pages().rec_lsn(second.pid()).unwrap_or_default();
+
+    for _ in 0..4 {
+      let mut filler = txns.begin();
+      txns.try_write(&mut filler, &mut second, 100, 100, &mut io::repeat(3))?;
+      txns.try_commit(filler)?;
+    }
+
+    let end = txns.try_checkpoint()?;
+    let dirty = vec![(first.pid(), first_lsn), (second.pid(), running_lsn)];
+    match wal.try_records(end)?.first() {
+      Some((_, WalRecord::EndCheckpoint(_, recorded, active))) => {
+        assert_eq!(&dirty, recorded);
+        assert_eq!(&vec![(running.id(), running_lsn)], active);
+      }
+
+      record => panic!("Expected the end of the checkpoint, found {:?}", record)
+    }
+
+    // The first page pins the log from the very first segment
+    assert_eq!(Some(end), wal.try_last_checkpoint()?);
+    assert_eq!(0, wal.try_segments()?[0]);
+
+    pages.try_flush_oldest(1)?;
+    assert_eq!(None, pages.dirty_pages().rec_lsn(first.pid()));
+    txns.try_checkpoint()?;
+    assert_eq!(running_lsn / wal.segment_len(), wal.try_segments()?[0]);
+
+    // Once nothing is dirty or running the log only keeps what the newest checkpoint needs
+    txns.try_commit(running)?;
+    pages.try_flush_oldest(8)?;
+    let end = txns.try_checkpoint()?;
+    assert_eq!((end - 1) / wal.segment_len(), wal.try_segments()?[0]);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
@@ -0,0 +1,107 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::Mutex;
+
+use std::{
+  collections::VecDeque,
+  sync::Arc
+};
+
+use crate::ReadPredicate;
+
+// Attribute of writes that weren't made to an attribute's value, like raw page bytes
+pub const OPAQUE_ATTRIBUTE: usize = usize::MAX;
+
+// Attribute, before-image and after-image of one update
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteImage(usize, Vec<u8>, Vec<u8>);
+
+impl WriteImage {
+  pub fn attribute(&self) -> usize {
+    self.0
+  }
+
+  pub fn before(&self) -> &[u8] {
+    &self.1
+  }
+
+  pub fn after(&self) -> &[u8] {
+    &self.2
+  }
+
+  pub fn is_opaque(&self) -> bool {
+    self.attribute() == OPAQUE_ATTRIBUTE
+  }
+
+  pub fn new(attribute: usize, before: &[u8], after: &[u8]) -> Self {
+    Self(attribute, before.to_vec(), after.to_vec())
+  }
+
+  // A write no predicate can tell apart from the values it reads
+  pub fn opaque() -> Self {
+    Self(OPAQUE_ATTRIBUTE, vec![], vec![])
+  }
+}
+
+//
+// Precision locking (see docs/acid/mvcc-hyper.md): the writes of recently
+//  committed transactions are kept until every transaction that was running
+//  when they committed has finished, a committing transaction is only
+//  serializable if none of the writes committed during its lifetime has a
+//  before or after image its read predicates contain
+//
+
+// Commit time and writes of every recently committed transaction, oldest first
+#[derive(Debug, Default)]
+pub struct CommitLog(Mutex<VecDeque<(usize, Arc<Vec<WriteImage>>)>>);
+
+impl CommitLog {
+  pub fn len(&self) -> usize {
+    self.0.lock().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn record(&self, committed_at: usize, writes: Vec<WriteImage>) {
+    if !writes.is_empty() {
+      self.0.lock().push_back((committed_at, Arc::new(writes)));
+    }
+  }
+
+  pub fn try_validate(&self, started_at: usize, predicates: &[ReadPredicate]) -> Result<()> {
+    if predicates.is_empty() {
+      return Ok(())
+    }
+
+    for (committed_at, writes) in self.0.lock().iter().filter(|(committed_at, _)| *committed_at > started_at) {
+      for write in writes.iter() {
+        let hit = predicates.iter().find(|predicate| {
+          write.is_opaque() || predicate.contains(write.attribute(), write.before()) || predicate.contains(write.attribute(), write.after())
+        });
+
+        if let Some(predicate) = hit {
+          return Err(anyhow!("Read of attribute {} was changed by a commit at {}", predicate.attribute(), committed_at))
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  // Drops writes no running transaction started before
+  pub fn prune(&self, horizon: usize) {
+    let mut log = self.0.lock();
+
+    while log.front().is_some_and(|(committed_at, _)| *committed_at <= horizon) {
+      log.pop_front();
+    }
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
@@ -0,0 +1,44 @@
+use std::ops::Bound;
+
+//
+// A range of values of one attribute a transaction read, values compare as
+//  bytes so numbers should be encoded big endian to keep their order
+//
+
+// Attribute, lower and upper bound
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadPredicate(usize, Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+impl ReadPredicate {
+  pub fn attribute(&self) -> usize {
+    self.0
+  }
+
+  pub fn contains(&self, attribute: usize, value: &[u8]) -> bool {
+    let above = match &self.1 {
+      Bound::Included(lo) => value >= lo.as_slice(),
+      Bound::Excluded(lo) => value > lo.as_slice(),
+      Bound::Unbounded => true
+    };
+
+    let below = match &self.2 {
+      Bound::Included(hi) => value <= hi.as_slice(),
+      Bound::Excluded(hi) => value < hi.as_slice(),
+      Bound::Unbounded => true
+    };
+
+    attribute == self.attribute() && above && below
+  }
+
+  pub fn all(attribute: usize) -> Self {
+    Self(attribute, Bound::Unbounded, Bound::Unbounded)
+  }
+
+  pub fn point(attribute: usize, value: &[u8]) -> Self {
+    Self(attribute, Bound::Included(value.to_vec()), Bound::Included(value.to_vec()))
+  }
+
+  pub fn range(attribute: usize, lo: Bound<Vec<u8>>, hi: Bound<Vec<u8>>) -> Self {
+    Self(attribute, lo, hi)
+  }
+}
@@ -0,0 +1,31 @@
+//
+// A position in a transaction: rolling back to it undoes the updates and page
+//  versions that came after and keeps everything before, it stays usable
+//  until the transaction rolls back past it
+//
+
+// Savepoint id, undo entries, page versions and write images when it was taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Savepoint(usize, usize, usize, usize);
+
+impl Savepoint {
+  pub fn id(&self) -> usize {
+    self.0
+  }
+
+  pub fn undo_len(&self) -> usize {
+    self.1
+  }
+
+  pub fn writes_len(&self) -> usize {
+    self.2
+  }
+
+  pub fn images_len(&self) -> usize {
+    self.3
+  }
+
+  pub fn new(id: usize, undo_len: usize, writes_len: usize, images_len: usize) -> Self {
+    Self(id, undo_len, writes_len, images_len)
+  }
+}
@@ -0,0 +1,92 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use crate::{ ReadPredicate, Savepoint, UndoBuffer, WriteImage };
+
+// Transaction id, snapshot time, pages it wrote, undo buffer, read predicates, write images and savepoints
+#[derive(Debug)]
+pub struct Transaction(usize, usize, Vec<usize>, UndoBuffer, Vec<ReadPredicate>, Vec<WriteImage>, Vec<Savepoint>);
+
+impl Transaction {
+  pub fn id(&self) -> usize {
+    self.0
+  }
+
+  pub fn started_at(&self) -> usize {
+    self.1
+  }
+
+  // Heads of the version chains this transaction stamped with its id, once per version it pushed
+  pub fn writes(&self) -> &[usize] {
+    &self.2
+  }
+
+  // Pages written since the newest savepoint
+  pub fn writes_since_savepoint(&self) -> &[usize] {
+    &self.2[self.6.last().map_or(0, Savepoint::writes_len)..]
+  }
+
+  pub fn savepoints(&self) -> &[Savepoint] {
+    &self.6
+  }
+
+  pub fn undo(&self) -> &UndoBuffer {
+    &self.3
+  }
+
+  pub fn undo_mut(&mut self) -> &mut UndoBuffer {
+    &mut self.3
+  }
+
+  pub fn predicates(&self) -> &[ReadPredicate] {
+    &self.4
+  }
+
+  pub fn images(&self) -> &[WriteImage] {
+    &self.5
+  }
+
+  // Commit fails if another transaction commits a write the predicate contains in the meantime
+  pub fn read(&mut self, predicate: ReadPredicate) {
+    self.4.push(predicate);
+  }
+
+  pub fn record_image(&mut self, image: WriteImage) {
+    self.5.push(image);
+  }
+
+  pub fn into_parts(self) -> (UndoBuffer, Vec<WriteImage>) {
+    (self.3, self.5)
+  }
+
+  pub fn record_write(&mut self, pid: usize) {
+    if !self.writes_since_savepoint().contains(&pid) {
+      self.2.push(pid);
+    }
+  }
+
+  pub fn savepoint(&mut self) -> Savepoint {
+    let id = self.6.last().map_or(0, |savepoint| savepoint.id() + 1);
+    let savepoint = Savepoint::new(id, self.3.entries().len(), self.2.len(), self.5.len());
+    self.6.push(savepoint);
+
+    savepoint
+  }
+
+  // Forgets what came after the savepoint, the caller undoes it first
+  pub fn try_truncate(&mut self, savepoint: Savepoint) -> Result<()> {
+    let idx = self.6.iter().position(|held| *held == savepoint)
+      .ok_or_else(|| anyhow!("Savepoint {} of transaction {} was rolled back past", savepoint.id(), self.id()))?;
+
+    self.2.truncate(savepoint.writes_len());
+    self.5.truncate(savepoint.images_len());
+    self.6.truncate(idx + 1);
+
+    Ok(())
+  }
+
+  pub fn new(id: usize, started_at: usize) -> Self {
+    Self(id, started_at, vec![], UndoBuffer::new(), vec![], vec![], vec![])
+  }
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use std::io::{ Read, Write };
+
+use crate::{ PageGuard, Transaction, TransactionManager };
+
+//
+// A page written on behalf of a transaction. Every write claims its bytes
+//  first so a second writer is aborted on the spot, and keeps what it
+//  replaced in the transaction's undo buffer so an abort puts it back
+//
+
+#[derive(Debug)]
+pub struct TxnWriteGuard<'t, 'g, 'a>(&'t TransactionManager, &'t mut Transaction, &'g mut PageGuard<'a>);
+
+impl<'t, 'g, 'a> TxnWriteGuard<'t, 'g, 'a> {
+  pub fn txn(&self) -> &Transaction {
+    self.1
+  }
+
+  pub fn read<W: Write>(&mut self, offset: usize, len: usize, dest: &mut W) -> Result<usize> {
+    self.2.try_write()?.read(offset, len, dest)
+  }
+
+  pub fn write<R: Read>(&mut self, offset: usize, len: usize, data: &mut R) -> Result<usize> {
+    self.0.try_write(self.1, self.2, offset, len, data)
+  }
+
+  pub fn new(txns: &'t TransactionManager, txn: &'t mut Transaction, page: &'g mut PageGuard<'a>) -> Self {
+    Self(txns, txn, page)
+  }
+}
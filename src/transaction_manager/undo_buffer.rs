@@ -0,0 +1,289 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::io::Cursor;
+
+use crate::{
+  HEADER_LEN, PageManager
+};
+
+// Undo pages hold at least this many bytes of entries
+pub const UNDO_PAGE_LEN: usize = 2usize.pow(16) - HEADER_LEN;
+
+// Stamp, data page, predecessor, value offset, pointer offset and before-image length
+pub const UNDO_ENTRY_LEN: usize = 40;
+
+// Pointer offset of an entry for bytes written in place that no version vector points to
+pub const UNDO_NO_POINTER: usize = u32::MAX as usize;
+
+//
+// Before-images of the values a transaction updated in place (see
+//  docs/acid/mvcc-hyper.md), appended to pages allocated from PageManager.
+//  An entry is addressed by its undo page PID in the high bits and its
+//  offset in the low 32 bits, which is what version vectors point to
+//
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndoEntry(usize, usize, usize, u32, u32, Vec<u8>);
+
+impl UndoEntry {
+  // Id of the transaction that replaced this value until it commits, its commit time after
+  pub fn stamp(&self) -> usize {
+    self.0
+  }
+
+  pub fn pid(&self) -> usize {
+    self.1
+  }
+
+  // The entry with the value this before-image replaced, 0 when there is none
+  pub fn next(&self) -> usize {
+    self.2
+  }
+
+  pub fn value_offset(&self) -> usize {
+    self.3 as usize
+  }
+
+  pub fn pointer_offset(&self) -> usize {
+    self.4 as usize
+  }
+
+  pub fn before(&self) -> &[u8] {
+    &self.5
+  }
+
+  // Encoded length in bytes
+  pub fn len(&self) -> usize {
+    UNDO_ENTRY_LEN + self.before().len()
+  }
+
+  // True when the value it replaced was empty
+  pub fn is_empty(&self) -> bool {
+    self.before().is_empty()
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(self.len());
+    bytes.extend_from_slice(&(self.stamp() as u64).to_be_bytes());
+    bytes.extend_from_slice(&(self.pid() as u64).to_be_bytes());
+    bytes.extend_from_slice(&(self.next() as u64).to_be_bytes());
+    bytes.extend_from_slice(&self.3.to_be_bytes());
+    bytes.extend_from_slice(&self.4.to_be_bytes());
+    bytes.extend_from_slice(&(self.before().len() as u64).to_be_bytes());
+    bytes.extend_from_slice(self.before());
+    bytes
+  }
+
+  // Points the entry at another predecessor, 0 ends its chain
+  pub fn try_set_next(pages: &PageManager, pointer: usize, next: usize) -> Result<()> {
+    let (pid, offset) = unpack_pointer(pointer);
+    let bytes = (next as u64).to_be_bytes();
+    pages.try_fetch(pid)?.try_write()?.write(offset + 16, bytes.len(), &mut Cursor::new(&bytes))?;
+
+    Ok(())
+  }
+
+  pub fn try_read(pages: &PageManager, pointer: usize) -> Result<Self> {
+    let (pid, offset) = unpack_pointer(pointer);
+    let mut page = pages.try_fetch(pid)?;
+    let page = page.try_write()?;
+
+    let corrupt = || anyhow!("Undo entry at {} runs past its page", pointer);
+    let bytes = page.bytes().get(HEADER_LEN + offset..).ok_or_else(corrupt)?;
+    let u32_at = |at: usize| -> Result<u32> { Ok(u32::from_be_bytes(bytes.get(at..at + 4).ok_or_else(corrupt)?.try_into()?)) };
+    let u64_at = |at: usize| -> Result<u64> { Ok(u64::from_be_bytes(bytes.get(at..at + 8).ok_or_else(corrupt)?.try_into()?)) };
+
+    let len = usize::try_from(u64_at(32)?)?;
+    let before = UNDO_ENTRY_LEN.checked_add(len).and_then(|end| bytes.get(UNDO_ENTRY_LEN..end)).ok_or_else(corrupt)?;
+
+    Ok(Self(u64_at(0)? as usize, u64_at(8)? as usize, u64_at(16)? as usize, u32_at(24)?, u32_at(28)?, before.to_vec()))
+  }
+
+  pub fn new(stamp: usize, pid: usize, next: usize, value_offset: usize, pointer_offset: usize, before: &[u8]) -> Self {
+    Self(stamp, pid, next, value_offset as u32, pointer_offset as u32, before.to_vec())
+  }
+}
+
+// Undo pages, bytes used and available in the newest one, a pointer to every entry in order and the page each is for
+#[derive(Debug, Default)]
+pub struct UndoBuffer(Vec<usize>, usize, usize, Vec<usize>, Vec<usize>);
+
+impl UndoBuffer {
+  pub fn pages(&self) -> &[usize] {
+    &self.0
+  }
+
+  pub fn entries(&self) -> &[usize] {
+    &self.3
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries().is_empty()
+  }
+
+  // True when an entry holds bytes of the page that were written over in place
+  pub fn is_undoing(&self, pid: usize) -> bool {
+    self.4.contains(&pid)
+  }
+
+  pub fn try_push(&mut self, pages: &PageManager, entry: &UndoEntry) -> Result<usize> {
+    let bytes = entry.to_bytes();
+
+    if self.pages().is_empty() || self.1 + bytes.len() > self.2 {
+      let len = UNDO_PAGE_LEN.max(bytes.len());
+      self.0.push(pages.try_alloc(len as u32)?.pid());
+      self.1 = 0;
+      self.2 = len;
+    }
+
+    let pid = *self.pages().last().ok_or_else(|| anyhow!("Undo buffer has no pages"))?;
+    let pointer = try_pack_pointer(pid, self.1)?;
+
+    pages.try_fetch(pid)?.try_write()?.write(self.1, bytes.len(), &mut Cursor::new(&bytes))?;
+    self.1 += bytes.len();
+    self.3.push(pointer);
+    self.4.push(entry.pid());
+
+    Ok(pointer)
+  }
+
+  // Marks every entry with the commit time so later snapshots skip them
+  pub fn try_stamp(&self, pages: &PageManager, stamp: usize) -> Result<()> {
+    for pointer in self.entries() {
+      let (pid, offset) = unpack_pointer(*pointer);
+      let bytes = (stamp as u64).to_be_bytes();
+      pages.try_fetch(pid)?.try_write()?.write(offset, bytes.len(), &mut Cursor::new(&bytes))?;
+    }
+
+    Ok(())
+  }
+
+  pub fn try_rollback(&self, pages: &PageManager) -> Result<()> {
+    self.try_restore(pages, 0)
+  }
+
+  // Rolls back and drops the entries pushed after the first len
+  pub fn try_rollback_to(&mut self, pages: &PageManager, len: usize) -> Result<()> {
+    self.try_restore(pages, len)?;
+    self.3.truncate(len);
+    self.4.truncate(len);
+
+    Ok(())
+  }
+
+  //
+  // Cuts every entry out of the chains that lead to it so nothing points at
+  //  it anymore, only once every snapshot sees the values the buffer's
+  //  transaction committed. A reader stops at the first entry it sees and
+  //  these are seen by everyone, so a chain that ended at one of them reads
+  //  the same without it
+  //
+  pub fn try_unlink(&self, pages: &PageManager) -> Result<()> {
+    for pointer in self.entries() {
+      let entry = UndoEntry::try_read(pages, *pointer)?;
+      if entry.pointer_offset() == UNDO_NO_POINTER {
+        continue
+      }
+
+      let mut page = pages.try_fetch(entry.pid())?;
+      let mut newer = {
+        let page = page.try_write()?;
+        let at = HEADER_LEN + entry.pointer_offset();
+        let pointer = page.bytes().get(at..at + 8).ok_or_else(|| anyhow!("Undo entry at {} points past page {}", pointer, entry.pid()))?;
+        u64::from_be_bytes(pointer.try_into()?) as usize
+      };
+
+      if newer == *pointer {
+        page.try_write()?.write(entry.pointer_offset(), 8, &mut Cursor::new(0u64.to_be_bytes()))?;
+        continue
+      }
+
+      while newer != 0 {
+        let next = UndoEntry::try_read(pages, newer)?.next();
+        if next == *pointer {
+          UndoEntry::try_set_next(pages, newer, 0)?;
+          break
+        }
+
+        newer = next;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn try_free(self, pages: &PageManager) -> Result<()> {
+    for pid in self.pages() {
+      pages.try_free(pages.try_fetch(*pid)?)?;
+    }
+
+    Ok(())
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Writes the before-images back newest first and points the version vectors past them, an entry that fails doesn't stop the rest
+  fn try_restore(&self, pages: &PageManager, from: usize) -> Result<()> {
+    let mut restored = Ok(());
+    for pointer in self.entries()[from..].iter().rev() {
+      restored = restored.and(Self::try_restore_entry(pages, *pointer));
+    }
+
+    restored
+  }
+
+  fn try_restore_entry(pages: &PageManager, pointer: usize) -> Result<()> {
+    let entry = UndoEntry::try_read(pages, pointer)?;
+    let mut page = pages.try_fetch(entry.pid())?;
+    let mut page = page.try_write()?;
+
+    page.write(entry.value_offset(), entry.before().len(), &mut Cursor::new(entry.before()))?;
+    if entry.pointer_offset() != UNDO_NO_POINTER {
+      page.write(entry.pointer_offset(), 8, &mut Cursor::new((entry.next() as u64).to_be_bytes()))?;
+    }
+
+    Ok(())
+  }
+}
+
+// PIDs and offsets get 32 bits each, a PID past that would alias a lower one
+pub fn try_pack_pointer(pid: usize, offset: usize) -> Result<usize> {
+  if pid > u32::MAX as usize || offset > u32::MAX as usize {
+    return Err(anyhow!("Undo entry at {} of page {} can't be addressed in 32 bits", offset, pid))
+  }
+
+  Ok((pid << 32) | offset)
+}
+
+pub fn unpack_pointer(pointer: usize) -> (usize, usize) {
+  (pointer >> 32, pointer & 0xFFFF_FFFF)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_try_read_rejects_entries_past_their_page() -> Result<()> {
+    let pages = PageManager::try_new(2usize.pow(31))?;
+    let mut buffer = UndoBuffer::new();
+    let pointer = buffer.try_push(&pages, &UndoEntry::new(7, 3, 0, 16, UNDO_NO_POINTER, &[1, 2, 3]))?;
+    assert_eq!(&[1, 2, 3], UndoEntry::try_read(&pages, pointer)?.before());
+
+    // A before-image length that runs off the page
+    let (pid, offset) = unpack_pointer(pointer);
+    pages.try_fetch(pid)?.try_write()?.write(offset + 32, 8, &mut Cursor::new(u64::MAX.to_be_bytes()))?;
+    assert!(UndoEntry::try_read(&pages, pointer).is_err());
+
+    // An entry that starts past the end of the page
+    let len = pages.try_fetch(pid)?.try_write()?.len();
+    assert!(UndoEntry::try_read(&pages, try_pack_pointer(pid, len)?).is_err());
+    assert!(UndoEntry::try_read(&pages, try_pack_pointer(pid, len - HEADER_LEN - 8)?).is_err());
+
+    buffer.try_free(&pages)
+  }
+}
@@ -0,0 +1,149 @@
+use anyhow::{
+  anyhow, Result
+};
+
+use std::io::Cursor;
+
+use crate::{
+  HEADER_LEN, PageChain, PageGuard, PageManager, UndoEntry
+};
+
+// Lowest and highest slot that has a version pointer
+pub const SYNOPSIS_LEN: usize = 8;
+
+//
+// Data pages updated in place keep one version pointer per value slot ahead
+//  of the values, 0 when the value is current and the newest undo entry
+//  otherwise, and a synopsis of which slots have any pointer at all so
+//  scans can skip the vector (see docs/acid/mvcc-hyper.md)
+//
+// Page data: synopsis | pointer per slot | value per slot
+//
+
+// Slots, bytes per value and the attribute the values belong to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionVector(usize, usize, usize);
+
+impl VersionVector {
+  pub fn attribute(&self) -> usize {
+    self.2
+  }
+
+  pub fn slots(&self) -> usize {
+    self.0
+  }
+
+  pub fn value_len(&self) -> usize {
+    self.1
+  }
+
+  // Bytes of page data the synopsis, vector and values take up
+  pub fn data_len(&self) -> usize {
+    self.values_offset() + self.slots() * self.value_len()
+  }
+
+  pub fn pointer_offset(&self, slot: usize) -> usize {
+    SYNOPSIS_LEN + slot * 8
+  }
+
+  pub fn value_offset(&self, slot: usize) -> usize {
+    self.values_offset() + slot * self.value_len()
+  }
+
+  // Clears the synopsis and every pointer of a freshly allocated page
+  pub fn try_init(&self, page: &mut PageGuard) -> Result<()> {
+    let mut area = vec![0u8; self.values_offset()];
+    area[0..4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+    page.try_write()?.write(0, area.len(), &mut Cursor::new(&area))?;
+    Ok(())
+  }
+
+  pub fn try_synopsis(&self, page: &mut PageGuard) -> Result<Option<(usize, usize)>> {
+    let page = page.try_write()?;
+    let bytes = &page.bytes()[HEADER_LEN..HEADER_LEN + SYNOPSIS_LEN];
+    let (lo, hi) = (u32::from_be_bytes(bytes[0..4].try_into()?), u32::from_be_bytes(bytes[4..8].try_into()?));
+
+    Ok(if lo > hi { None } else { Some((lo as usize, hi as usize)) })
+  }
+
+  // The in-place value and its version pointer
+  pub fn try_slot(&self, page: &mut PageGuard, slot: usize) -> Result<(Vec<u8>, usize)> {
+    self.try_check_slot(slot)?;
+    let page = page.try_write()?;
+    let bytes = &page.bytes()[HEADER_LEN..];
+
+    let pointer = u64::from_be_bytes(bytes[self.pointer_offset(slot)..self.pointer_offset(slot) + 8].try_into()?) as usize;
+    let value = bytes[self.value_offset(slot)..self.value_offset(slot) + self.value_len()].to_vec();
+
+    Ok((value, pointer))
+  }
+
+  // Writes the value in place and points its slot at the undo entry holding what it replaced
+  pub fn try_update(&self, page: &mut PageGuard, slot: usize, value: &[u8], pointer: usize) -> Result<()> {
+    self.try_check_slot(slot)?;
+    if value.len() != self.value_len() {
+      return Err(anyhow!("Value of {} bytes doesn't fit a {} byte slot", value.len(), self.value_len()))
+    }
+
+    let synopsis = self.try_synopsis(page)?;
+    let (lo, hi) = synopsis.map_or((slot, slot), |(lo, hi)| (lo.min(slot), hi.max(slot)));
+
+    let mut page = page.try_write()?;
+    page.write(0, 4, &mut Cursor::new((lo as u32).to_be_bytes()))?;
+    page.write(4, 4, &mut Cursor::new((hi as u32).to_be_bytes()))?;
+    page.write(self.pointer_offset(slot), 8, &mut Cursor::new((pointer as u64).to_be_bytes()))?;
+    page.write(self.value_offset(slot), value.len(), &mut Cursor::new(value))?;
+
+    Ok(())
+  }
+
+  //
+  // The value the transaction sees: starting from the in-place value v, undo
+  //  entries are applied until v.P = null || v.P.TS = T || v.P.TS < T.startTime
+  //  where a stamp at or before the snapshot counts as before the start time
+  //
+  pub fn try_read_visible(&self, pages: &PageManager, page: &mut PageGuard, slot: usize, snapshot: usize, txn: usize) -> Result<Vec<u8>> {
+    let (mut value, mut pointer) = self.try_slot(page, slot)?;
+
+    while pointer != 0 {
+      let entry = UndoEntry::try_read(pages, pointer)?;
+      if PageChain::is_visible(entry.stamp(), snapshot, txn) {
+        break
+      }
+
+      value = entry.before().to_vec();
+      pointer = entry.next();
+    }
+
+    Ok(value)
+  }
+
+  // As many slots as fit the page data next to their pointers
+  pub fn fit(data_len: usize, value_len: usize) -> Self {
+    Self::new(data_len.saturating_sub(SYNOPSIS_LEN) / (8 + value_len), value_len)
+  }
+
+  pub fn with_attribute(mut self, attribute: usize) -> Self {
+    self.2 = attribute;
+    self
+  }
+
+  pub fn new(slots: usize, value_len: usize) -> Self {
+    Self(slots, value_len, 0)
+  }
+
+  // Private Helpers
+
+  fn values_offset(&self) -> usize {
+    SYNOPSIS_LEN + self.slots() * 8
+  }
+
+  fn try_check_slot(&self, slot: usize) -> Result<()> {
+    if slot < self.slots() {
+      Ok(())
+    } else {
+      Err(anyhow!("Slot {} is out of range for {} slots", slot, self.slots()))
+    }
+  }
+}
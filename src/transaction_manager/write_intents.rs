@@ -0,0 +1,170 @@
+use anyhow::Result;
+use parking_lot::Mutex;
+
+use std::{
+  collections::HashMap,
+  error::Error,
+  fmt
+};
+
+// Page, offset and length of the contested range and the transaction that holds it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteConflict(usize, usize, usize, usize);
+
+impl WriteConflict {
+  pub fn pid(&self) -> usize {
+    self.0
+  }
+
+  pub fn offset(&self) -> usize {
+    self.1
+  }
+
+  pub fn len(&self) -> usize {
+    self.2
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn holder(&self) -> usize {
+    self.3
+  }
+}
+
+impl fmt::Display for WriteConflict {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Bytes {}..{} of page {} are being written by {}", self.offset(), self.offset() + self.len(), self.pid(), self.holder())
+  }
+}
+
+impl Error for WriteConflict {}
+
+//
+// First writer wins at (page, offset) granularity: a transaction claims the
+//  bytes it writes until it commits or aborts, and anyone else writing over
+//  them in the meantime loses. Committed claims stay until every snapshot
+//  is at least as new as the commit, so a transaction that started before
+//  the commit loses too instead of overwriting it
+//
+
+// Claimed range as start, end, transaction and the time it committed at once it did
+type Intent = (usize, usize, usize, Option<usize>);
+
+// Claimed ranges by page and pages with running claims by transaction
+type Intents = (HashMap<usize, Vec<Intent>>, HashMap<usize, Vec<usize>>);
+
+#[derive(Debug, Default)]
+pub struct WriteIntents(Mutex<Intents>);
+
+impl WriteIntents {
+  pub fn len(&self) -> usize {
+    self.0.lock().0.values().map(Vec::len).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // Claims the bytes for a transaction that started at the snapshot
+  pub fn try_acquire(&self, txn: usize, started_at: usize, pid: usize, offset: usize, len: usize) -> Result<()> {
+    let (start, end) = (offset, offset + len);
+    let mut intents = self.0.lock();
+    let (by_page, by_txn) = &mut *intents;
+    let ranges = by_page.entry(pid).or_default();
+
+    let held = ranges.iter().find(|(lo, hi, holder, committed_at)| {
+      let contested = match committed_at {
+        Some(committed_at) => *committed_at > started_at,
+        None => *holder != txn
+      };
+
+      contested && *lo < end && start < *hi
+    });
+
+    if let Some((_, _, holder, _)) = held {
+      return Err(WriteConflict(pid, offset, len, *holder).into())
+    }
+
+    if !ranges.iter().any(|(lo, hi, holder, committed_at)| *holder == txn && committed_at.is_none() && *lo <= start && end <= *hi) {
+      ranges.push((start, end, txn, None));
+
+      let pids = by_txn.entry(txn).or_default();
+      if !pids.contains(&pid) {
+        pids.push(pid);
+      }
+    }
+
+    Ok(())
+  }
+
+  // Keeps the transaction's claims against anyone whose snapshot is older than the commit
+  pub fn commit(&self, txn: usize, committed_at: usize) {
+    let mut intents = self.0.lock();
+    let (by_page, by_txn) = &mut *intents;
+
+    for pid in by_txn.remove(&txn).unwrap_or_default() {
+      for intent in by_page.get_mut(&pid).into_iter().flatten().filter(|intent| intent.2 == txn) {
+        intent.3 = Some(committed_at);
+      }
+    }
+  }
+
+  pub fn release(&self, txn: usize) {
+    let mut intents = self.0.lock();
+    let (by_page, by_txn) = &mut *intents;
+
+    for pid in by_txn.remove(&txn).unwrap_or_default() {
+      if let Some(ranges) = by_page.get_mut(&pid) {
+        ranges.retain(|(_, _, holder, committed_at)| *holder != txn || committed_at.is_some());
+        if ranges.is_empty() {
+          by_page.remove(&pid);
+        }
+      }
+    }
+  }
+
+  // Drops the committed claims every snapshot from the horizon on already sees
+  pub fn prune(&self, horizon: usize) {
+    self.0.lock().0.retain(|_, ranges| {
+      ranges.retain(|(_, _, _, committed_at)| committed_at.is_none_or(|committed_at| committed_at > horizon));
+      !ranges.is_empty()
+    });
+  }
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_claims_conflict_on_overlapping_bytes_only() -> Result<()> {
+    let intents = WriteIntents::new();
+    intents.try_acquire(1, 0, 7, 0, 8)?;
+    intents.try_acquire(1, 0, 7, 2, 4)?;
+    assert_eq!(1, intents.len());
+
+    // Neighbouring bytes and other pages are free, one shared byte is not
+    intents.try_acquire(2, 0, 7, 8, 8)?;
+    intents.try_acquire(2, 0, 8, 0, 8)?;
+    let conflict = intents.try_acquire(2, 0, 7, 7, 1).unwrap_err();
+    assert_eq!(Some(&WriteConflict(7, 7, 1, 1)), conflict.downcast_ref::<WriteConflict>());
+
+    // The winner's claims outlive its commit for snapshots older than it
+    intents.commit(1, 5);
+    intents.release(2);
+    assert!(intents.try_acquire(3, 4, 7, 0, 1).is_err());
+    intents.try_acquire(3, 5, 7, 0, 1)?;
+
+    intents.prune(5);
+    intents.release(3);
+    assert!(intents.is_empty());
+
+    Ok(())
+  }
+}
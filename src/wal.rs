@@ -0,0 +1,286 @@
+mod wal_record;
+
+use anyhow::{
+  anyhow, Result
+};
+
+use parking_lot::Mutex;
+
+use std::{
+  fs::{ self, File, OpenOptions },
+  io::Write,
+  path::{ Path, PathBuf }
+};
+
+pub use wal_record::*;
+
+// Records never span segments so every segment starts at a multiple of this
+pub const WAL_SEGMENT_LEN: usize = 2usize.pow(26);
+
+// Record length and checksum ahead of every record
+pub const WAL_FRAME_HEADER_LEN: usize = 8;
+
+// LSN of the newest END_CHECKPOINT record, replaced by atomic rename
+pub const WAL_CHECKPOINT_FILE: &str = "checkpoint";
+
+//
+// The write-ahead log is a directory of fixed size segments and an LSN is
+//  the position of a record in the log as a whole, so segment n holds the
+//  LSNs from n * segment_len up to the next segment. A record that doesn't
+//  fit in what is left of a segment starts the next one, the gap reads as a
+//  record of length 0
+//
+// <wal>/
+//   segment-<n>  records as [len][checksum][record]
+//   checkpoint   LSN of the last complete fuzzy checkpoint
+//
+
+// Directory, segment length and the open segment with the LSN of the next record
+#[derive(Debug)]
+pub struct Wal(PathBuf, usize, Mutex<(File, usize)>);
+
+impl Wal {
+  pub fn dir(&self) -> &Path {
+    &self.0
+  }
+
+  pub fn segment_len(&self) -> usize {
+    self.1
+  }
+
+  // The LSN the next record gets
+  pub fn next_lsn(&self) -> usize {
+    self.2.lock().1
+  }
+
+  pub fn segment_path(&self, segment: usize) -> PathBuf {
+    Self::segment_path_in(self.dir(), segment)
+  }
+
+  pub fn try_segments(&self) -> Result<Vec<usize>> {
+    Self::try_segments_in(self.dir())
+  }
+
+  // Appends the record to the open segment and returns its LSN, it isn't durable until the next try_sync
+  pub fn try_append(&self, record: &WalRecord) -> Result<usize> {
+    let bytes = record.to_bytes();
+    let frame_len = WAL_FRAME_HEADER_LEN + bytes.len();
+
+    if frame_len > self.segment_len() {
+      return Err(anyhow!("A {} byte record doesn't fit in a write-ahead log segment", bytes.len()))
+    }
+
+    let mut frame = Vec::with_capacity(frame_len);
+    frame.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&checksum(&bytes).to_be_bytes());
+    frame.extend_from_slice(&bytes);
+
+    let mut tail = self.2.lock();
+
+    if tail.1 % self.segment_len() + frame_len > self.segment_len() {
+      let segment = tail.1 / self.segment_len() + 1;
+      tail.0.sync_data()?;
+      tail.0 = Self::try_open_append(&self.segment_path(segment))?;
+      tail.1 = segment * self.segment_len();
+    }
+
+    let lsn = tail.1;
+    tail.0.write_all(&frame)?;
+    tail.1 += frame_len;
+
+    Ok(lsn)
+  }
+
+  pub fn try_sync(&self) -> Result<()> {
+    Ok(self.2.lock().0.sync_data()?)
+  }
+
+  // Every record from the LSN on in log order
+  pub fn try_records(&self, from: usize) -> Result<Vec<(usize, WalRecord)>> {
+    let mut records = vec![];
+
+    for segment in self.try_segments()? {
+      let base = segment * self.segment_len();
+      if base + self.segment_len() <= from {
+        continue
+      }
+
+      let bytes = fs::read(self.segment_path(segment))?;
+      let mut at = 0;
+
+      while let Some(record) = read_frame(&bytes, at) {
+        if base + at >= from {
+          records.push((base + at, WalRecord::try_from_bytes(record)?));
+        }
+
+        at += WAL_FRAME_HEADER_LEN + record.len();
+      }
+    }
+
+    Ok(records)
+  }
+
+  //
+  // Deletes every segment whose records all come before the LSN and returns
+  //  how many went, the segment being appended to always stays
+  //
+  pub fn try_truncate(&self, lsn: usize) -> Result<usize> {
+    let open = self.next_lsn() / self.segment_len();
+    let mut removed = 0;
+
+    for segment in self.try_segments()? {
+      if segment < open && (segment + 1) * self.segment_len() <= lsn {
+        fs::remove_file(self.segment_path(segment))?;
+        removed += 1;
+      }
+    }
+
+    if removed > 0 {
+      File::open(self.dir())?.sync_all()?;
+    }
+
+    Ok(removed)
+  }
+
+  // Where recovery starts scanning from, None before the first checkpoint
+  pub fn try_last_checkpoint(&self) -> Result<Option<usize>> {
+    let path = self.dir().join(WAL_CHECKPOINT_FILE);
+    if !path.exists() {
+      return Ok(None)
+    }
+
+    let bytes = fs::read(&path)?;
+    let lsn = bytes.get(0..8).ok_or_else(|| anyhow!("Checkpoint file {} is truncated", path.display()))?;
+    Ok(Some(u64::from_be_bytes(lsn.try_into()?) as usize))
+  }
+
+  // Write + fsync a sibling file then rename it over the checkpoint file
+  pub fn try_set_checkpoint(&self, lsn: usize) -> Result<()> {
+    let path = self.dir().join(WAL_CHECKPOINT_FILE);
+    let tmp_path = path.with_extension("tmp");
+
+    {
+      let mut tmp = File::create(&tmp_path)?;
+      tmp.write_all(&(lsn as u64).to_be_bytes())?;
+      tmp.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, &path)?;
+    File::open(self.dir())?.sync_all()?;
+
+    Ok(())
+  }
+
+  pub fn try_open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+    Self::try_open_with(dir, WAL_SEGMENT_LEN)
+  }
+
+  // Appends after the last complete record of the newest segment, a torn record at the tail is cut off
+  pub fn try_open_with<P: AsRef<Path>>(dir: P, segment_len: usize) -> Result<Self> {
+    let dir = dir.as_ref().to_path_buf();
+    fs::create_dir_all(&dir)?;
+
+    let segment = Self::try_segments_in(&dir)?.last().copied().unwrap_or(0);
+    let path = Self::segment_path_in(&dir, segment);
+
+    let bytes = if path.exists() { fs::read(&path)? } else { vec![] };
+    let mut end = 0;
+    while let Some(record) = read_frame(&bytes, end) {
+      end += WAL_FRAME_HEADER_LEN + record.len();
+    }
+
+    let file = Self::try_open_append(&path)?;
+    file.set_len(end as u64)?;
+
+    Ok(Self(dir, segment_len, Mutex::new((file, segment * segment_len + end))))
+  }
+
+  // Segment files of the log in the directory, for callers that haven't opened it
+  pub fn segment_path_in(dir: &Path, segment: usize) -> PathBuf {
+    dir.join(format!("segment-{:020}", segment))
+  }
+
+  pub fn try_segments_in(dir: &Path) -> Result<Vec<usize>> {
+    let mut segments = vec![];
+
+    for entry in fs::read_dir(dir)? {
+      let name = entry?.file_name();
+      if let Some(segment) = name.to_str().and_then(|name| name.strip_prefix("segment-")) {
+        segments.push(segment.parse::<usize>()?);
+      }
+    }
+
+    segments.sort_unstable();
+    Ok(segments)
+  }
+
+  // Private Helpers
+
+  fn try_open_append(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+  }
+}
+
+// The record at the offset, None at the end of the segment or a torn record
+fn read_frame(bytes: &[u8], at: usize) -> Option<&[u8]> {
+  let len = u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?) as usize;
+  let sum = u32::from_be_bytes(bytes.get(at + 4..at + 8)?.try_into().ok()?);
+  let record = bytes.get(at + WAL_FRAME_HEADER_LEN..at + WAL_FRAME_HEADER_LEN + len)?;
+
+  (len > 0 && checksum(record) == sum).then_some(record)
+}
+
+// FNV-1a, enough to tell a torn record from a complete one
+fn checksum(bytes: &[u8]) -> u32 {
+  bytes.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vex-wal-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+  }
+
+  #[test]
+  fn test_append_reopen_and_truncate() -> Result<()> {
+    let dir = temp_dir("segments");
+    let wal = Wal::try_open_with(&dir, 200)?;
+
+    let lsns = (0..8)
+      .map(|txn| wal.try_append(&WalRecord::Update(txn, txn + 1, 0, vec![txn as u8; 40])))
+      .collect::<Result<Vec<usize>>>()?;
+    wal.try_sync()?;
+
+    // Two 81 byte records per segment
+    assert_eq!(vec![0, 81, 200, 281, 400, 481, 600, 681], lsns);
+    assert_eq!(4, wal.try_segments()?.len());
+
+    let records = wal.try_records(lsns[5])?;
+    assert_eq!(3, records.len());
+    assert_eq!((lsns[5], WalRecord::Update(5, 6, 0, vec![5; 40])), records[0]);
+
+    // A torn record at the tail is dropped on open
+    let mut file = OpenOptions::new().append(true).open(wal.segment_path(3))?;
+    file.write_all(&[0, 0, 0, 9, 1, 2])?;
+    drop(wal);
+
+    let wal = Wal::try_open_with(&dir, 200)?;
+    assert_eq!(762, wal.next_lsn());
+    assert_eq!(8, wal.try_records(0)?.len());
+
+    assert_eq!(2, wal.try_truncate(lsns[5])?);
+    assert_eq!(vec![2, 3], wal.try_segments()?);
+    assert_eq!(4, wal.try_records(0)?.len());
+
+    assert_eq!(None, wal.try_last_checkpoint()?);
+    wal.try_set_checkpoint(lsns[6])?;
+    assert_eq!(Some(lsns[6]), Wal::try_open_with(&dir, 200)?.try_last_checkpoint()?);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
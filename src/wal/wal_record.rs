@@ -0,0 +1,120 @@
+use anyhow::{
+  anyhow, Result
+};
+
+//
+// Every record is a kind byte followed by big-endian words
+//
+// Update(txn, pid, offset, redo)           - bytes the transaction wrote into the page at the offset
+// Commit(txn)                              - the transaction committed
+// Abort(txn)                               - the transaction rolled back
+// BeginCheckpoint                          - a fuzzy checkpoint started at this LSN
+// EndCheckpoint(begin, dirty, active)      - the dirty page table (PID, Rec LSN) and active
+//                                            transaction table (TXN ID, First LSN) it copied
+//
+
+const UPDATE_KIND: u8 = 1;
+const COMMIT_KIND: u8 = 2;
+const ABORT_KIND: u8 = 3;
+const BEGIN_CHECKPOINT_KIND: u8 = 4;
+const END_CHECKPOINT_KIND: u8 = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalRecord {
+  Update(usize, usize, usize, Vec<u8>),
+  Commit(usize),
+  Abort(usize),
+  BeginCheckpoint,
+  EndCheckpoint(usize, Vec<(usize, usize)>, Vec<(usize, usize)>)
+}
+
+impl WalRecord {
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = vec![];
+    let put = |bytes: &mut Vec<u8>, word: usize| bytes.extend_from_slice(&(word as u64).to_be_bytes());
+
+    let kind = match self {
+      Self::Update(txn, pid, offset, redo) => {
+        put(&mut bytes, *txn);
+        put(&mut bytes, *pid);
+        put(&mut bytes, *offset);
+        put(&mut bytes, redo.len());
+        bytes.extend_from_slice(redo);
+        UPDATE_KIND
+      }
+
+      Self::Commit(txn) => {
+        put(&mut bytes, *txn);
+        COMMIT_KIND
+      }
+
+      Self::Abort(txn) => {
+        put(&mut bytes, *txn);
+        ABORT_KIND
+      }
+
+      Self::BeginCheckpoint => BEGIN_CHECKPOINT_KIND,
+
+      Self::EndCheckpoint(begin, dirty, active) => {
+        put(&mut bytes, *begin);
+
+        for table in [dirty, active] {
+          put(&mut bytes, table.len());
+          for (key, lsn) in table {
+            put(&mut bytes, *key);
+            put(&mut bytes, *lsn);
+          }
+        }
+
+        END_CHECKPOINT_KIND
+      }
+    };
+
+    bytes.insert(0, kind);
+    bytes
+  }
+
+  pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+    let corrupt = || anyhow!("Malformed write-ahead log record");
+    let mut at = 1;
+    let mut word = || -> Result<usize> {
+      let word = bytes.get(at..at + 8).ok_or_else(corrupt)?;
+      at += 8;
+      Ok(u64::from_be_bytes(word.try_into()?) as usize)
+    };
+
+    let record = match bytes.first() {
+      Some(&UPDATE_KIND) => {
+        let (txn, pid, offset, len) = (word()?, word()?, word()?, word()?);
+        let redo = bytes.get(33..).filter(|redo| redo.len() == len).ok_or_else(corrupt)?;
+        Self::Update(txn, pid, offset, redo.to_vec())
+      }
+
+      Some(&COMMIT_KIND) => Self::Commit(word()?),
+      Some(&ABORT_KIND) => Self::Abort(word()?),
+      Some(&BEGIN_CHECKPOINT_KIND) => Self::BeginCheckpoint,
+
+      Some(&END_CHECKPOINT_KIND) => {
+        let begin = word()?;
+        let mut tables = vec![];
+
+        for _ in 0..2 {
+          let len = word()?;
+          if len > bytes.len() / 16 {
+            return Err(corrupt())
+          }
+
+          tables.push((0..len).map(|_| Ok((word()?, word()?))).collect::<Result<Vec<_>>>()?);
+        }
+
+        let active = tables.pop().unwrap_or_default();
+        Self::EndCheckpoint(begin, tables.pop().unwrap_or_default(), active)
+      }
+
+      Some(kind) => return Err(anyhow!("Unknown write-ahead log record kind {}", kind)),
+      None => return Err(corrupt())
+    };
+
+    Ok(record)
+  }
+}